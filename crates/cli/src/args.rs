@@ -0,0 +1,68 @@
+//! A minimal parser for `<positional>... --option <value> --switch` style arguments.
+
+use super::Failure;
+
+/// The arguments following a command.
+pub struct Args {
+	positional : Vec<String>,
+	values : Vec<(String, String)>,
+	switches : Vec<String>,
+}
+
+impl Args {
+	/// Splits `args` into positional arguments and options, which are given as `--name value`, `--name=value` or `--switch`.
+	///
+	/// # Arguments
+	/// * `positional` - How many positional arguments the command takes.
+	/// * `takes_value` - Names of the options followed by a value, e.g. `include` for `--include <glob>`.
+	/// * `switches` - Names of the options which stand alone, e.g. `quiet` for `--quiet`.
+	pub fn parse(args : &[String], positional : usize, takes_value : &[&str], switches : &[&str]) -> Result<Self, Failure> {
+		let mut parsed = Args { positional : Vec::new(), values : Vec::new(), switches : Vec::new() };
+
+		let mut args = args.iter();
+		while let Some(arg) = args.next() {
+			let Some(option) = arg.strip_prefix("--") else {
+				parsed.positional.push(arg.clone());
+				continue;
+			};
+			let (name, inline_value) = match option.split_once('=') {
+				Some((name, value)) => (name, Some(value.to_string())),
+				None => (option, None),
+			};
+
+			if takes_value.contains(&name) {
+				let value = inline_value.or_else(|| args.next().cloned()).ok_or_else(|| Failure::Usage(format!("--{} needs a value", name)))?;
+				parsed.values.push((name.to_string(), value));
+			} else if switches.contains(&name) && inline_value.is_none() {
+				parsed.switches.push(name.to_string());
+			} else {
+				return Err(Failure::Usage(format!("Unknown option \"{}\"", arg)));
+			}
+		}
+
+		if parsed.positional.len() != positional {
+			return Err(Failure::Usage(format!("Expected {} arguments but got {}", positional, parsed.positional.len())));
+		}
+		Ok(parsed)
+	}
+
+	/// The positional argument at `index`, which `parse` checked is present.
+	pub fn positional(&self, index : usize) -> &str {
+		&self.positional[index]
+	}
+
+	/// The last value given for the option `name`.
+	pub fn value(&self, name : &str) -> Option<&str> {
+		self.values.iter().rev().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+	}
+
+	/// Every value given for the option `name`, in order.
+	pub fn values(&self, name : &str) -> Vec<String> {
+		self.values.iter().filter(|(n, _)| n == name).map(|(_, v)| v.clone()).collect()
+	}
+
+	/// Whether the switch `name` was given.
+	pub fn switch(&self, name : &str) -> bool {
+		self.switches.iter().any(|s| s == name)
+	}
+}
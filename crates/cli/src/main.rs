@@ -1,9 +1,382 @@
-extern crate valve_resource_tools;
+//! `vrst`, a command line tool for inspecting, extracting, creating and validating VPKs.
 
-use valve_resource_tools::*;
-#[allow(unused_imports)]
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use valve_resource_tools::resource::error::ErrorKind;
+use valve_resource_tools::resource::vpk::{self, VPK, VPKv2, DirectoryFilter, DiffOptions, ChangeKind, EntryLocation};
+use valve_resource_tools::resource::vpk::v2::{self, CreateOptionsV2, PackRules, SigningKeyV2, VALVE_ARCHIVE_MD5_BLOCK_SIZE};
 use valve_resource_tools::resource::vpk::prelude::*;
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+mod args;
+use args::Args;
+
+const USAGE : &str = "\
+Usage: vrst <command> [options]
+
+Commands:
+  list <vpk>                  List every entry with its size and location
+  extract <vpk> <folder>      Extract entries to a folder
+    --include <glob>          Only extract matching entries, may be repeated
+    --exclude <glob>          Skip matching entries, may be repeated
+    --quiet                   Don't print each extracted entry
+  create <folder> <output>    Pack a folder into <output>_dir.vpk and its data archives
+    --include <glob>          Only pack matching files, may be repeated
+    --exclude <glob>          Skip matching files, may be repeated
+    --chunk-size <bytes>      Size at which a new data archive is started
+    --single-file             Write a standalone <output>.vpk with all data embedded
+    --reproducible            Write entries in path order for byte identical output
+    --deduplicate             Store the data of identical files once
+    --md5-blocks              Write archive checksums per 1MiB block as Valve's tools do
+    --rules <file>            JSON pack rules setting preload, storage and groups
+    --key <file>              Sign with a PEM private key or a Valve .privatekey.vdf
+  validate <vpk>              Check the archive MD5s, OtherMD5s, entry CRCs and signature
+    --key <file>              Require the signature to be made by a PEM public key or a Valve .publickey.vdf
+  info <vpk>                  Show the header, data archive count and signature status
+    --key <file>              Check the signature against a PEM public key or a Valve .publickey.vdf
+  cat <vpk> <entry>           Write the data of one entry to stdout
+  manifest <vpk>              Write the structure of a V2 VPK as JSON to stdout
+    --output <file>           Write to a file instead
+  diff <old> <new>            List the entries added, removed, modified or moved to another data archive between two VPKs
+    --data                    Compare entry data byte for byte rather than by CRC
+    --summary                 Only print the number of each kind of change
+    --json                    Print the differences as JSON
+  help                        Show this message
+
+<vpk> is a _dir.vpk, one of its numbered data archives or a single file VPK.
+Signatures are checked the way create signs VPKs, which hasn't been confirmed to match paks signed by Valve.
+";
+
+/// Why a command failed.
+enum Failure {
+	/// The arguments were invalid, the usage is shown with the message.
+	Usage(String),
+	/// The library returned an error.
+	Error(ErrorKind),
+	/// A check failed, the details have already been printed.
+	Invalid,
+}
+
+impl From<ErrorKind> for Failure {
+	fn from(e : ErrorKind) -> Self {
+		Failure::Error(e)
+	}
+}
+
+impl From<std::io::Error> for Failure {
+	fn from(e : std::io::Error) -> Self {
+		Failure::Error(ErrorKind::IO(e))
+	}
+}
+
+fn main() -> ExitCode {
+	let args : Vec<String> = std::env::args().skip(1).collect();
+	match run(&args) {
+		Ok(()) => ExitCode::SUCCESS,
+		Err(Failure::Usage(message)) => {
+			eprintln!("vrst: {}\n\n{}", message, USAGE);
+			ExitCode::from(2)
+		},
+		Err(Failure::Error(ErrorKind::IO(e))) if e.kind() == std::io::ErrorKind::BrokenPipe => ExitCode::SUCCESS, /* Output closed early, e.g. piped to head */
+		Err(Failure::Error(e)) => {
+			eprintln!("vrst: {}", e);
+			ExitCode::FAILURE
+		},
+		Err(Failure::Invalid) => ExitCode::FAILURE,
+	}
+}
+
+fn run(args : &[String]) -> Result<(), Failure> {
+	let Some((command, args)) = args.split_first() else {
+		return Err(Failure::Usage("No command given".to_string()));
+	};
+
+	match command.as_str() {
+		"list" => list(Args::parse(args, 1, &[], &[])?),
+		"extract" => extract(Args::parse(args, 2, &["include", "exclude"], &["quiet"])?),
+		"create" => create(Args::parse(
+			args, 2,
+			&["include", "exclude", "chunk-size", "rules", "key"],
+			&["single-file", "reproducible", "deduplicate", "md5-blocks"],
+		)?),
+		"validate" => validate(Args::parse(args, 1, &["key"], &[])?),
+		"info" => info(Args::parse(args, 1, &["key"], &[])?),
+		"cat" => cat(Args::parse(args, 2, &[], &[])?),
+		"manifest" => manifest(Args::parse(args, 1, &["output"], &[])?),
+		"diff" => diff(Args::parse(args, 2, &[], &["data", "summary", "json"])?),
+		"help" | "--help" | "-h" => {
+			print!("{}", USAGE);
+			Ok(())
+		},
+		other => Err(Failure::Usage(format!("Unknown command \"{}\"", other))),
+	}
+}
+
+fn list(args : Args) -> Result<(), Failure> {
+	let vpk = vpk::open(Path::new(args.positional(0)))?;
+
+	let mut out = std::io::stdout().lock();
+	writeln!(out, "{:>10} {:>7} {:>7} {:>10}  Path", "Size", "Preload", "Archive", "Offset")?;
+	for handle in vpk.get_handles() {
+		let (archive, offset) = if handle.is_preload_only() {
+			("-".to_string(), "-".to_string())
+		} else if handle.is_embedded() {
+			("dir".to_string(), handle.offset().to_string())
+		} else {
+			(format!("{:0>3}", handle.archive_index()), handle.offset().to_string())
+		};
+		writeln!(out, "{:>10} {:>7} {:>7} {:>10}  {}", handle.total_size(), handle.preload_size(), archive, offset, handle.path())?;
+	}
+	Ok(())
+}
+
+fn extract(args : Args) -> Result<(), Failure> {
+	let vpk = vpk::open(Path::new(args.positional(0)))?;
+	let filter = DirectoryFilter { include : args.values("include"), exclude : args.values("exclude") };
+	let quiet = args.switch("quiet");
+
+	let mut out = std::io::stdout().lock();
+	let summary = vpk.extract_all(Path::new(args.positional(1)), &filter, |progress| {
+		if !quiet {
+			let _ = writeln!(out, "{}", progress.path); /* Listing is best effort, extraction continues if stdout is closed */
+		}
+		true
+	})?;
+
+	eprintln!("Extracted {} entries, {} bytes", summary.entries, summary.bytes);
+	Ok(())
+}
+
+fn create(args : Args) -> Result<(), Failure> {
+	let single_file = args.switch("single-file");
+	let (directory_path, filename) = split_output(Path::new(args.positional(1)), single_file)?;
+
+	let mut options = CreateOptionsV2 {
+		single_file,
+		reproducible : args.switch("reproducible"),
+		deduplicate : args.switch("deduplicate"),
+		..Default::default()
+	};
+	if let Some(size) = args.value("chunk-size") {
+		options.chunk_size = size.parse().map_err(|_| Failure::Usage(format!("Invalid chunk size \"{}\"", size)))?;
+	}
+	if args.switch("md5-blocks") {
+		options.archive_md5_block_size = Some(VALVE_ARCHIVE_MD5_BLOCK_SIZE);
+	}
+	if let Some(rules) = args.value("rules") {
+		options.pack_rules = Some(PackRules::open(Path::new(rules))?);
+	}
+	if let Some(key) = args.value("key") {
+		options.signing_key = Some(read_signing_key(Path::new(key))?);
+	}
+	let filter = DirectoryFilter { include : args.values("include"), exclude : args.values("exclude") };
+
+	std::fs::create_dir_all(&directory_path)?;
+	let summary = VPKv2::create_from_directory(Path::new(args.positional(0)), &directory_path, &filename, &filter, &options)?;
+	if options.deduplicate {
+		eprintln!("Deduplicated {} entries, saving {} bytes", summary.deduplicated_entries, summary.bytes_saved);
+	}
+	Ok(())
+}
+
+/// Splits the output given to `create` into its folder and base name, accepting `pak01`, `pak01_dir.vpk` or `addon.vpk`.
+fn split_output(output : &Path, single_file : bool) -> Result<(PathBuf, String), Failure> {
+	let name = output.file_name().and_then(|n| n.to_str()).ok_or_else(|| Failure::Usage(format!("Invalid output \"{}\"", output.display())))?;
+	let name = name.strip_suffix(".vpk").unwrap_or(name);
+	let name = if single_file { name } else { name.strip_suffix("_dir").unwrap_or(name) };
+
+	let folder = output.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or_else(|| Path::new("."));
+	Ok((folder.to_path_buf(), name.to_string()))
+}
+
+/// Reads a PEM private key, or a Valve `.privatekey.vdf` with its `.publickey.vdf` alongside it.
+fn read_signing_key(path : &Path) -> Result<SigningKeyV2, Failure> {
+	let private_key = std::fs::read_to_string(path)?;
+	match path.to_string_lossy().strip_suffix(".privatekey.vdf") {
+		Some(base) => {
+			let public_key = std::fs::read_to_string(format!("{}.publickey.vdf", base))?;
+			Ok(SigningKeyV2::from_valve_key_files(&private_key, &public_key)?)
+		},
+		None => Ok(SigningKeyV2::from_pem(&private_key)?),
+	}
+}
+
+fn validate(args : Args) -> Result<(), Failure> {
+	let vpk = vpk::open(Path::new(args.positional(0)))?;
+	let mut valid = true;
+
+	match &vpk {
+		VPK::V1(_) => {
+			println!("OtherMD5:    not present in V1");
+			println!("Archive MD5: not present in V1");
+		},
+		VPK::V2(v2) => {
+			match v2.validate_other() {
+				Ok(()) => println!("OtherMD5:    ok"),
+				Err(e) => {
+					valid = false;
+					println!("OtherMD5:    FAILED, {}", e);
+				},
+			}
+
+			let failed = v2.validate_archive();
+			if failed.is_empty() {
+				println!("Archive MD5: ok, {} checksums", v2.archive_md5_entries().len());
+			} else {
+				valid = false;
+				println!("Archive MD5: FAILED, {} of {} checksums", failed.len(), v2.archive_md5_entries().len());
+				for c in failed {
+					println!("  archive {:0>3} offset {} count {}", c.archive_index, c.starting_offset, c.count);
+				}
+			}
+		},
+	}
+
+	match vpk.validate_entries() {
+		Ok(failed) if failed.is_empty() => println!("CRC:         ok, {} entries", vpk.get_handles().len()),
+		Ok(failed) => {
+			valid = false;
+			println!("CRC:         FAILED, {} of {} entries", failed.len(), vpk.get_handles().len());
+			for path in failed {
+				println!("  {}", path);
+			}
+		},
+		Err(e) => {
+			valid = false;
+			println!("CRC:         FAILED, {}", e);
+		},
+	}
+
+	let trusted_key = args.value("key").map(|key| read_trusted_key(Path::new(key))).transpose()?;
+	match validate_signature(&vpk, trusted_key.as_deref()) {
+		Ok(()) if trusted_key.is_some() => println!("Signature:   ok, trusted key"),
+		Ok(()) => println!("Signature:   ok, embedded key only"),
+		Err(ErrorKind::DoesNotExist(_)) => println!("Signature:   not signed"),
+		Err(e) => {
+			valid = false;
+			println!("Signature:   FAILED, {}", e);
+		},
+	}
+
+	if valid { Ok(()) } else { Err(Failure::Invalid) }
+}
+
+fn info(args : Args) -> Result<(), Failure> {
+	let vpk = vpk::open(Path::new(args.positional(0)))?;
+
+	match &vpk {
+		VPK::V1(v1) => {
+			println!("Version:                  1");
+			println!("Entries:                  {}", vpk.get_handles().len());
+			println!("Data archives:            {}", v1.archive_count());
+			println!("Tree size:                {}", v1.header().tree_size);
+		},
+		VPK::V2(v2) => {
+			let header = v2.header();
+			println!("Version:                  2");
+			println!("Entries:                  {}", vpk.get_handles().len());
+			println!("Data archives:            {}", v2.archive_count());
+			println!("Tree size:                {}", header.tree_size);
+			println!("Embedded data size:       {}", header.file_data_section_size);
+			println!("Archive MD5 section size: {} ({} checksums)", header.archive_md5_section_size, v2.archive_md5_entries().len());
+			println!("Other MD5 section size:   {}", header.other_md5_section_size);
+			println!("Signature section size:   {}", header.signature_section_size);
+		},
+	}
+
+	let trusted_key = args.value("key").map(|key| read_trusted_key(Path::new(key))).transpose()?;
+	let signature = match validate_signature(&vpk, trusted_key.as_deref()) {
+		Ok(()) if trusted_key.is_some() => "signed, matches the trusted key".to_string(),
+		Ok(()) => "signed, matches the embedded key".to_string(),
+		Err(ErrorKind::DoesNotExist(_)) => "not signed".to_string(),
+		Err(e) => format!("signed, doesn't match ({})", e),
+	};
+	println!("Signature:                {}", signature);
+	Ok(())
+}
+
+/// Checks the signature against the trusted key if given, otherwise only against the key embedded in the VPK.
+fn validate_signature(vpk : &VPK, trusted_key : Option<&[u8]>) -> Result<(), ErrorKind> {
+	match trusted_key {
+		Some(key) => vpk.validate_signature_with_key(key),
+		None => vpk.validate_signature(),
+	}
+}
+
+/// Reads a PEM public key, or a Valve `.publickey.vdf`.
+fn read_trusted_key(path : &Path) -> Result<Vec<u8>, Failure> {
+	let public_key = std::fs::read_to_string(path)?;
+	if path.to_string_lossy().ends_with(".publickey.vdf") {
+		Ok(v2::public_key_from_valve_key_file(&public_key)?)
+	} else {
+		Ok(v2::public_key_from_pem(&public_key)?)
+	}
+}
+
+fn cat(args : Args) -> Result<(), Failure> {
+	let vpk = vpk::open(Path::new(args.positional(0)))?;
+	let mut reader = vpk.get_entry_from_path(args.positional(1))?;
+
+	let mut out = std::io::stdout().lock();
+	std::io::copy(&mut reader, &mut out)?;
+	out.flush()?;
+	Ok(())
+}
+
+fn manifest(args : Args) -> Result<(), Failure> {
+	let json = match vpk::open(Path::new(args.positional(0)))? {
+		VPK::V1(_) => return Err(Failure::Error(ErrorKind::Unsupported("Manifests of V1 VPKs".to_string()))),
+		VPK::V2(vpk) => vpk.manifest().to_json(),
+	};
+
+	match args.value("output") {
+		Some(path) => std::fs::write(path, json + "\n")?,
+		None => println!("{}", json),
+	}
+	Ok(())
+}
+
+fn diff(args : Args) -> Result<(), Failure> {
+	let old = vpk::open(Path::new(args.positional(0)))?;
+	let new = vpk::open(Path::new(args.positional(1)))?;
+	let diff = vpk::diff(&old, &new, &DiffOptions { compare_data : args.switch("data") })?;
+
+	let mut out = std::io::stdout().lock();
+	if args.switch("json") {
+		writeln!(out, "{}", diff.to_json())?;
+		return Ok(());
+	}
+
+	/// Describes where an entry's data is, e.g. `000@1024` or `dir@0`.
+	fn location(l : &EntryLocation) -> String {
+		match l.archive_index {
+			_ if l.size == u32::from(l.preload_size) => "preload".to_string(),
+			0x7fff => format!("dir@{}", l.offset),
+			index => format!("{:0>3}@{}", index, l.offset),
+		}
+	}
+
+	if !args.switch("summary") {
+		for change in &diff.changes {
+			let detail = match (change.kind, &change.old, &change.new) {
+				(ChangeKind::Modified, Some(old), Some(new)) => format!("size {} -> {}, crc {:08x} -> {:08x}", old.size, new.size, old.crc, new.crc),
+				(ChangeKind::Moved, Some(old), Some(new)) => format!("{} -> {}", location(old), location(new)),
+				(_, Some(l), None) | (_, None, Some(l)) => format!("size {}, {}", l.size, location(l)),
+				_ => String::new(),
+			};
+			let kind = match change.kind {
+				ChangeKind::Added => "added",
+				ChangeKind::Removed => "removed",
+				ChangeKind::Modified => "modified",
+				ChangeKind::Moved => "moved",
+			};
+			writeln!(out, "{:<8}  {}  ({})", kind, change.path, detail)?;
+		}
+	}
+	writeln!(
+		out, "{} added, {} removed, {} modified, {} moved, {} unchanged",
+		diff.count(ChangeKind::Added), diff.count(ChangeKind::Removed), diff.count(ChangeKind::Modified), diff.count(ChangeKind::Moved), diff.unchanged,
+	)?;
 	Ok(())
-}
\ No newline at end of file
+}
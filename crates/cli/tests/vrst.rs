@@ -0,0 +1,134 @@
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+fn vrst(args : &[&str]) -> Output {
+	Command::new(env!("CARGO_BIN_EXE_vrst")).args(args).output().expect("Couldn't run vrst")
+}
+
+fn get_example_path(name : &str) -> String {
+	concat!(env!("CARGO_MANIFEST_DIR"), "/../lib/test-data/").to_owned() + name
+}
+
+fn get_tmp_dir(name : &str) -> PathBuf {
+	let tmp_dir = std::env::temp_dir().join(format!("vrst-cli-{}-{}", name, std::process::id()));
+	let _ = std::fs::remove_dir_all(&tmp_dir);
+	std::fs::create_dir_all(&tmp_dir).unwrap();
+	tmp_dir
+}
+
+#[test]
+fn list_and_cat() {
+	let output = vrst(&["list", &get_example_path("vpk_test_dir.vpk")]);
+	assert!(output.status.success());
+	let listing = String::from_utf8(output.stdout).unwrap();
+	assert_eq!(listing.lines().count(), 5);
+	assert!(listing.lines().any(|l| l.ends_with("testing-folder/PreloadAndArchive.txt") && l.contains(" 000 ")));
+	assert!(listing.lines().any(|l| l.ends_with("testing-folder/EmbededArchiveOnly.txt") && l.contains(" dir ")));
+
+	let output = vrst(&["cat", &get_example_path("vpk_test_dir.vpk"), "testing-folder/PreloadAndArchive.txt"]);
+	assert!(output.status.success());
+	assert_eq!(output.stdout, std::fs::read(get_example_path("PreloadAndArchive.txt")).unwrap());
+
+	let output = vrst(&["cat", &get_example_path("vpk_test_dir.vpk"), "testing-folder/Missing.txt"]);
+	assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+/// Packs a folder, then checks it validates, describes and extracts back to the same files.
+fn create_validate_extract() {
+	let tmp_dir = get_tmp_dir("create");
+	let source = tmp_dir.join("source");
+	std::fs::create_dir_all(source.join("materials")).unwrap();
+	std::fs::write(source.join("materials/a.vmt"), "\"LightmappedGeneric\" {}").unwrap();
+	std::fs::write(source.join("readme.txt"), "not packed").unwrap();
+	std::fs::copy(get_example_path("PreloadAndArchive.txt"), source.join("materials/b.txt")).unwrap();
+
+	let output_path = tmp_dir.join("pak01_dir.vpk");
+	let output = vrst(&["create", source.to_str().unwrap(), output_path.to_str().unwrap(), "--exclude", "readme.txt", "--key", &get_example_path("vpk_signing_key.pem")]);
+	assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+	assert!(tmp_dir.join("pak01_000.vpk").is_file());
+
+	let output = vrst(&["validate", output_path.to_str().unwrap()]);
+	assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stdout));
+
+	let output = vrst(&["info", output_path.to_str().unwrap()]);
+	let info = String::from_utf8(output.stdout).unwrap();
+	assert!(info.contains("Entries:                  2"));
+	assert!(info.contains("Data archives:            1"));
+	assert!(info.contains("Signature:                signed, matches the embedded key"));
+
+	let output = vrst(&["validate", output_path.to_str().unwrap(), "--key", &get_example_path("vpk_signing_key.publickey.vdf")]);
+	assert!(String::from_utf8(output.stdout).unwrap().contains("Signature:   ok, trusted key"));
+	let output = vrst(&["info", output_path.to_str().unwrap(), "--key", &get_example_path("vpk_signing_key.pem")]);
+	assert_eq!(output.status.code(), Some(1)); /* A private key isn't accepted as the trusted key */
+
+	let target = tmp_dir.join("out");
+	let output = vrst(&["extract", output_path.to_str().unwrap(), target.to_str().unwrap(), "--include=*.txt", "--quiet"]);
+	assert!(output.status.success());
+	assert_eq!(std::fs::read(target.join("materials/b.txt")).unwrap(), std::fs::read(source.join("materials/b.txt")).unwrap());
+	assert!(!target.join("materials/a.vmt").exists());
+
+	std::fs::remove_dir_all(tmp_dir).unwrap();
+}
+
+#[test]
+fn failures_exit_non_zero() {
+	let tmp_dir = get_tmp_dir("failures");
+	let source = tmp_dir.join("source");
+	std::fs::create_dir_all(&source).unwrap();
+	std::fs::copy(get_example_path("ArchiveOnly.txt"), source.join("a.txt")).unwrap();
+
+	let output_path = tmp_dir.join("pak01_dir.vpk");
+	assert!(vrst(&["create", source.to_str().unwrap(), tmp_dir.join("pak01").to_str().unwrap()]).status.success());
+
+	/* Corrupt the archive data */
+	let mut data = std::fs::read(tmp_dir.join("pak01_000.vpk")).unwrap();
+	data[0] ^= 0xff;
+	std::fs::write(tmp_dir.join("pak01_000.vpk"), data).unwrap();
+	let output = vrst(&["validate", output_path.to_str().unwrap()]);
+	assert_eq!(output.status.code(), Some(1));
+	let report = String::from_utf8(output.stdout).unwrap();
+	assert!(report.contains("Archive MD5: FAILED"));
+	assert!(report.contains("CRC:         FAILED"));
+
+	assert_eq!(vrst(&["info", tmp_dir.join("missing_dir.vpk").to_str().unwrap()]).status.code(), Some(1));
+	assert_eq!(vrst(&["list"]).status.code(), Some(2));
+	assert_eq!(vrst(&["list", output_path.to_str().unwrap(), "--unknown"]).status.code(), Some(2));
+	assert_eq!(vrst(&["unpack"]).status.code(), Some(2));
+	assert_eq!(vrst(&[]).status.code(), Some(2));
+
+	std::fs::remove_dir_all(tmp_dir).unwrap();
+}
+
+#[test]
+fn manifest() {
+	let output = vrst(&["manifest", &get_example_path("vpk_signed_dir.vpk")]);
+	assert!(output.status.success());
+	let json = String::from_utf8(output.stdout).unwrap();
+	assert!(json.contains("\"schema_version\": 1"));
+	assert!(json.contains("\"path\": \"testing-folder/PreloadAndArchive.txt\""));
+
+	assert_eq!(vrst(&["manifest", &get_example_path("vpk_v1_test_dir.vpk")]).status.code(), Some(1));
+}
+
+#[test]
+fn diff() {
+	let output = vrst(&["diff", &get_example_path("vpk_v1_test_dir.vpk"), &get_example_path("vpk_test_dir.vpk"), "--data"]);
+	assert!(output.status.success());
+	assert_eq!(String::from_utf8(output.stdout).unwrap(), "0 added, 0 removed, 0 modified, 0 moved, 4 unchanged\n");
+
+	/* The V2 fixture has no CRCs so a changed byte is found by comparing data */
+	let tmp_dir = get_tmp_dir("diff");
+	std::fs::copy(get_example_path("vpk_test_dir.vpk"), tmp_dir.join("vpk_test_dir.vpk")).unwrap();
+	let mut data = std::fs::read(get_example_path("vpk_test_000.vpk")).unwrap();
+	data[0] ^= 0xff;
+	std::fs::write(tmp_dir.join("vpk_test_000.vpk"), data).unwrap();
+	let output = vrst(&["diff", &get_example_path("vpk_test_dir.vpk"), tmp_dir.join("vpk_test_dir.vpk").to_str().unwrap()]);
+	let report = String::from_utf8(output.stdout).unwrap();
+	assert!(report.lines().any(|l| l == "modified  testing-folder/ArchiveOnly.txt  (size 18 -> 18, crc 00000000 -> 00000000)"), "{}", report);
+	assert!(report.ends_with("0 added, 0 removed, 1 modified, 0 moved, 3 unchanged\n"));
+	std::fs::remove_dir_all(tmp_dir).unwrap();
+
+	let output = vrst(&["diff", &get_example_path("vpk_test_dir.vpk"), &get_example_path("vpk_test_dir.vpk"), "--json"]);
+	assert!(String::from_utf8(output.stdout).unwrap().contains("\"unchanged\": 4"));
+}
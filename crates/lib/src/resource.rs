@@ -11,6 +11,10 @@ pub mod error {
 		DoesNotExist(String),
 		AlreadyExists(String),
 		ValidationFailed(String),
+		NotVPK(String),
+		UnsupportedVersion(u32),
+		TooLarge(String),
+		Unsupported(String),
 		
 		/* Wrapped errors from other libs */
 		IO(std::io::Error),
@@ -26,6 +30,10 @@ pub mod error {
 				ErrorKind::DoesNotExist(e)     => write!(f, "Does Not Exist: {}", e),
 				ErrorKind::AlreadyExists(e)    => write!(f, "Already Exists: {}", e),
 				ErrorKind::ValidationFailed(e) => write!(f, "Validation Failed: {}", e),
+				ErrorKind::NotVPK(e)           => write!(f, "Not a VPK: {}", e),
+				ErrorKind::UnsupportedVersion(e) => write!(f, "Unsupported Version: {}", e),
+				ErrorKind::TooLarge(e)         => write!(f, "Too Large: {}", e),
+				ErrorKind::Unsupported(e)      => write!(f, "Unsupported: {}", e),
 				ErrorKind::Bincode(e)          => write!(f, "Bincode: {}", e),
 			}
 		}
@@ -7,15 +7,172 @@
 pub mod prelude {
 	pub use super::Open;
 	pub use super::Extract;
+	pub use super::ExtractAll;
+	pub use super::List;
 	pub use super::ValidateArchive;
 	pub use super::ValidateOther;
+	pub use super::ValidateEntries;
+	pub use super::ValidateSignature;
 }
 
-use std::{path::Path, rc::Rc};
+use std::{path::{Path, PathBuf}, sync::Arc, fs::File};
 use std::io::prelude::*;
 use super::{error::ErrorKind,*};
 
+pub use v1::VPKv1;
 pub use v2::VPKv2;
+pub use directory::Handle as EntryHandle;
+pub use directory::EntryBytes;
+pub use data::{ArchiveMD5SectionEntry, SignatureSection};
+pub use extract::{ExtractAll, ExtractProgress, ExtractSummary};
+pub use diff::{diff, DiffOptions, Diff, ChangeKind, EntryChange, EntryLocation};
+
+pub trait ReadSeek : Read + Seek {}
+impl ReadSeek for File {}
+impl ReadSeek for std::io::Cursor<&[u8]> {}
+
+/// A source of VPK data which can be read from any position without a shared cursor.
+/// 
+/// This allows many entries to be read at once from different threads.
+pub trait ReadAt : Send + Sync {
+	/// Reads bytes starting at `offset` into `buf`, returning how many were read.
+	fn read_at(&self, buf : &mut [u8], offset : u64) -> std::io::Result<usize>;
+
+	/// Reads exactly enough bytes starting at `offset` to fill `buf`.
+	/// 
+	/// # Errors
+	/// * `UnexpectedEof` - When the source ends before `buf` is filled.
+	fn read_exact_at(&self, mut buf : &mut [u8], mut offset : u64) -> std::io::Result<()> {
+		while !buf.is_empty() {
+			match self.read_at(buf, offset) {
+				Ok(0) => return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "failed to fill whole buffer")),
+				Ok(n) => {
+					buf = &mut buf[n..];
+					offset += u64::try_from(n).unwrap();
+				},
+				Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {},
+				Err(e) => return Err(e),
+			}
+		}
+		Ok(())
+	}
+
+	/// Returns the whole source if it is held in memory, allowing entries to be borrowed without copying.
+	fn as_bytes(&self) -> Option<&[u8]> {
+		None
+	}
+}
+
+impl ReadAt for File {
+	#[cfg(unix)]
+	fn read_at(&self, buf : &mut [u8], offset : u64) -> std::io::Result<usize> {
+		std::os::unix::fs::FileExt::read_at(self, buf, offset)
+	}
+
+	#[cfg(windows)]
+	fn read_at(&self, buf : &mut [u8], offset : u64) -> std::io::Result<usize> {
+		std::os::windows::fs::FileExt::seek_read(self, buf, offset)
+	}
+}
+
+/// Reads from an in memory buffer, shared by the `ReadAt` impls of byte containers.
+fn read_slice_at(source : &[u8], buf : &mut [u8], offset : u64) -> std::io::Result<usize> {
+	let start = usize::try_from(offset).unwrap_or(usize::MAX).min(source.len());
+	let n = std::cmp::min(buf.len(), source.len() - start);
+	buf[..n].copy_from_slice(&source[start..start + n]);
+	Ok(n)
+}
+
+impl ReadAt for memmap2::Mmap {
+	fn read_at(&self, buf : &mut [u8], offset : u64) -> std::io::Result<usize> {
+		read_slice_at(self, buf, offset)
+	}
+
+	fn as_bytes(&self) -> Option<&[u8]> {
+		Some(self)
+	}
+}
+
+impl ReadAt for Vec<u8> {
+	fn read_at(&self, buf : &mut [u8], offset : u64) -> std::io::Result<usize> {
+		read_slice_at(self, buf, offset)
+	}
+
+	fn as_bytes(&self) -> Option<&[u8]> {
+		Some(self)
+	}
+}
+
+impl ReadAt for &'static [u8] {
+	fn read_at(&self, buf : &mut [u8], offset : u64) -> std::io::Result<usize> {
+		read_slice_at(self, buf, offset)
+	}
+
+	fn as_bytes(&self) -> Option<&[u8]> {
+		Some(self)
+	}
+}
+
+/// Adapts any `Read + Seek` source to `ReadAt`.
+/// 
+/// Reads are serialised through a lock as the source has a single cursor.
+pub struct ReadSeekAt<R : Read + Seek + Send> {
+	inner : std::sync::Mutex<R>,
+}
+
+impl<R : Read + Seek + Send> ReadSeekAt<R> {
+	pub fn new(inner : R) -> Self {
+		Self { inner : std::sync::Mutex::new(inner) }
+	}
+
+	/// Returns the wrapped source.
+	pub fn into_inner(self) -> R {
+		self.inner.into_inner().unwrap_or_else(|e| e.into_inner())
+	}
+}
+
+impl<R : Read + Seek + Send> ReadAt for ReadSeekAt<R> {
+	fn read_at(&self, buf : &mut [u8], offset : u64) -> std::io::Result<usize> {
+		let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+		inner.seek(std::io::SeekFrom::Start(offset))?;
+		inner.read(buf)
+	}
+}
+
+/// A shared source of VPK data, see `ReadAt`.
+pub type Reader = Arc<dyn ReadAt>;
+
+/// Selects files by path when packing a folder or extracting entries.
+///
+/// Patterns are globs matched against paths using `/` separators, relative to the folder or VPK root, e.g. `materials/**/*.vmt`.
+/// `*` also matches across separators so `*.txt` matches text files at any depth.
+#[derive(Clone, Debug, Default)]
+pub struct DirectoryFilter {
+	/// Only files matching at least one of these are selected, every file is selected when empty.
+	pub include : Vec<String>,
+	/// Files matching any of these are not selected, even if included.
+	pub exclude : Vec<String>,
+}
+
+impl DirectoryFilter {
+	/// Compiles the patterns into a matcher.
+	///
+	/// # Errors
+	/// * `MalformedData` - When a pattern isn't a valid glob.
+	fn compile(&self) -> Result<impl Fn(&str) -> bool, ErrorKind> {
+		fn compile_all(patterns : &[String]) -> Result<Vec<helpers::PathPattern>, ErrorKind> {
+			patterns.iter().map(|p| helpers::PathPattern::new(p)).collect()
+		}
+
+		let include = compile_all(&self.include)?;
+		let exclude = compile_all(&self.exclude)?;
+
+		Ok(move |path : &str| {
+			(include.is_empty() || include.iter().any(|p| p.matches(path)))
+				&& !exclude.iter().any(|p| p.matches(path))
+		})
+	}
+}
 
 pub trait Open where Self : Sized {
 	fn open_from_path(path : &Path) -> Result<Self, ErrorKind>;
@@ -27,6 +184,19 @@ pub trait Extract where Self : Sized {
 	fn get_entry_from_path(&self, path : &str) -> Result<Self::EntryReader, ErrorKind>;
 }
 
+pub trait List {
+	/// Returns the handle of every entry in directory tree order.
+	fn get_handles(&self) -> Vec<&EntryHandle>;
+
+	/// Returns the handle of the entry at `path`, containing its metadata.
+	fn get_handle_from_path(&self, path : &str) -> Option<&EntryHandle>;
+
+	/// Returns the full path of every entry in directory tree order.
+	fn list_entries(&self) -> Vec<&str> {
+		self.get_handles().into_iter().map(|h| h.path()).collect()
+	}
+}
+
 /// This trait allows for VPK formats validate their resources.
 /// 
 /// Usually present past V1
@@ -41,17 +211,55 @@ pub trait ValidateOther {
 	fn validate_other(&self) -> Result<(), ErrorKind>;
 }
 
-mod v1;
+/// This trait allows for VPK formats to validate each entry against its CRC.
+pub trait ValidateEntries {
+	/// Returns `true` if the entry at `path` matches its CRC.
+	fn validate_entry(&self, path : &str) -> Result<bool, ErrorKind>;
+	/// Returns the paths of all entries that don't match their CRC.
+	fn validate_entries(&self) -> Result<Vec<&str>, ErrorKind>;
+}
+
+/// This trait allows for VPK formats to verify the signature of their directory file.
+pub trait ValidateSignature {
+	/// Verifies the embedded signature against the embedded public key.
+	/// 
+	/// This only shows the directory file matches its own signature. Anyone editing the file can sign it again with their own key,
+	/// use `validate_signature_with_key` to check who signed it. Signatures are checked the way this library signs VPKs,
+	/// which hasn't been confirmed to match paks signed by Valve.
+	/// 
+	/// # Errors
+	/// * `DoesNotExist` - When the VPK is not signed.
+	/// * `MalformedData` - When the public key or signature cannot be decoded.
+	/// * `ValidationFailed` - When the signature does not match the directory file.
+	fn validate_signature(&self) -> Result<(), ErrorKind>;
+
+	/// Verifies the embedded signature was made by the key the caller trusts, e.g. the publisher's.
+	/// 
+	/// # Arguments
+	/// * `public_key` - The trusted DER encoded public key, see `v2::public_key_from_pem` and `v2::public_key_from_valve_key_file`.
+	/// 
+	/// # Errors
+	/// * `DoesNotExist` - When the VPK is not signed.
+	/// * `MalformedData` - When the trusted key cannot be decoded.
+	/// * `ValidationFailed` - When the embedded public key isn't the trusted key or the signature does not match the directory file.
+	fn validate_signature_with_key(&self, public_key : &[u8]) -> Result<(), ErrorKind>;
+}
+
+mod diff;
+mod directory;
+mod extract;
+pub mod v1;
 pub mod v2;
 
 /// Pure data types used to read file sections.
 mod data {
 	use serde::{Serialize, Deserialize};
+	use crate::resource::error::ErrorKind;
 
 	/// Magic number present at the start of all VPKs.
 	pub const VPK_SIGNATURE : u32 = 0x55aa1234;
 
-	#[derive(Clone, Default, Serialize, Deserialize)]
+	#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 	pub struct ArchiveMD5SectionEntry {
 		pub archive_index : u32,
 		/// where to start reading bytes
@@ -70,64 +278,328 @@ mod data {
 		pub archive_md5_section_checksum : [u8; 16],
 		pub unknown : [u8; 16],
 	}
+
+	/// Represents the data structure following each filename in the VPK directory
+	#[derive(Clone, Default, Serialize, Deserialize)]
+	pub struct DirectoryEntryData {
+		/// A 32bit CRC of the file's data.
+		pub crc : u32,
+		/// The number of bytes contained in the index file.
+		pub preload_bytes_size : u16,
+		
+		/// A zero based index of the archive this file's data is contained in.
+		/// If 0x7fff, the data follows the directory.
+		pub archive_index : u16,
 	
-	#[repr(C, packed)]
-	pub struct SignatureSection {
-		/// always seen as 160 (0xA0) bytes
-		pub public_key_size : u32, 
-		pub public_key : [u8; 160],
+		/// If ArchiveIndex is 0x7fff, the offset of the file data relative to the end of the directory (see the header for more details).
+		/// Otherwise, the offset of the data from the start of the specified archive.
+		pub data_offset : u32,
 	
-		/// always seen as 128 (0x80) bytes
-		pub signature_size : u32, 
-		pub signature : [u8; 128],
+		/// If zero, the entire file is stored in the preload data.
+		/// Otherwise, the number of bytes stored starting at EntryOffset.
+		pub data_length : u32,
+	
+		/// Should always be 0xffff
+		pub terminator : u16,
+	}
+	
+	impl DirectoryEntryData {
+		pub const SIZE : usize = 18;
+		pub const TERMINATOR : u16 = 0xffff;
+		pub const DATA_IN_DIRECTORY_ARCHIVE_INDEX : u16 = 0x7fff;
+	
+		pub(in crate::resource::vpk) fn total_data_size(&self) -> u32 {
+			self.data_length + u32::from(self.preload_bytes_size)
+		}
+	
+		pub(in crate::resource::vpk) fn is_valid(&self) -> Result<(), ErrorKind> {
+			if self.terminator != Self::TERMINATOR { return Err(ErrorKind::MalformedData("Directory Entry Terminator".to_string())) }
+			if self.data_length.checked_add(u32::from(self.preload_bytes_size)).is_none() { return Err(ErrorKind::MalformedData("Directory Entry Size".to_string())) }
+			Ok(())
+		}
+	
+		pub(in crate::resource::vpk) fn is_in_directory_archive(&self) -> bool {
+			self.archive_index == Self::DATA_IN_DIRECTORY_ARCHIVE_INDEX
+		}
+	
+		pub(in crate::resource::vpk) fn is_preload_only(&self) -> bool {
+			self.data_length == 0
+		}
+	
+		pub(in crate::resource::vpk) fn has_preload(&self) -> bool {
+			self.preload_bytes_size != 0
+		}
+	}
+	
+	/// The public key and signature found at the end of a signed directory file.
+	#[derive(Clone, Debug, PartialEq, Eq)]
+	pub struct SignatureSection {
+		/// DER encoded RSA public key, always seen as 160 (0xA0) bytes
+		pub public_key : Vec<u8>,
+		/// RSA signature of the directory file, always seen as 128 (0x80) bytes
+		pub signature : Vec<u8>,
+	}
+
+	impl SignatureSection {
+		/// Parses a signature section made of a size prefixed public key followed by a size prefixed signature.
+		/// 
+		/// # Errors
+		/// * `MalformedData` - When the sizes do not add up to the length of `buf`.
+		pub(in crate::resource::vpk) fn from_bytes(buf : &[u8]) -> Result<Self, ErrorKind> {
+			fn take_sized<'a>(buf : &mut &'a [u8]) -> Result<&'a [u8], ErrorKind> {
+				if buf.len() < 4 { return Err(ErrorKind::MalformedData("Signature Section".to_string())); }
+				let size = usize::try_from(u32::from_le_bytes(buf[0..4].try_into().unwrap())).unwrap();
+				if buf.len() - 4 < size { return Err(ErrorKind::MalformedData("Signature Section".to_string())); }
+				let data = &buf[4..4 + size];
+				*buf = &buf[4 + size..];
+				Ok(data)
+			}
+
+			let mut buf = buf;
+			let public_key = take_sized(&mut buf)?.to_vec();
+			let signature = take_sized(&mut buf)?.to_vec();
+			if !buf.is_empty() { return Err(ErrorKind::MalformedData("Signature Section".to_string())); }
+
+			Ok(Self { public_key, signature })
+		}
+
+		/// Serializes the section in the layout read by `from_bytes`.
+		pub(in crate::resource::vpk) fn to_bytes(&self) -> Vec<u8> {
+			let mut buf = Vec::with_capacity(self.size());
+			buf.extend_from_slice(&u32::try_from(self.public_key.len()).unwrap().to_le_bytes());
+			buf.extend_from_slice(&self.public_key);
+			buf.extend_from_slice(&u32::try_from(self.signature.len()).unwrap().to_le_bytes());
+			buf.extend_from_slice(&self.signature);
+			buf
+		}
+
+		/// The size of the section in bytes once written.
+		pub fn size(&self) -> usize {
+			8 + self.public_key.len() + self.signature.len()
+		}
 	}
 }
 
 mod helpers {
 	use super::*;
 
-	pub(super) fn get_base_path(path : &Path) -> String {
-		let s = path.to_str().expect("could not convert path to &str");
-		s[0..s.len() - 7].to_owned()
+	/// How a VPK file name relates to the other files in its set.
+	#[derive(Debug, PartialEq, Eq)]
+	pub(super) enum ArchiveName<'a> {
+		/// `<base>_dir.vpk`, the directory file of a multi file set.
+		Directory(&'a str),
+		/// `<base>_NNN.vpk`, a data archive of a multi file set.
+		Chunk(&'a str, u16),
+		/// Any other name, e.g. `addon.vpk`, a single file VPK with all data embedded.
+		Standalone,
+	}
+
+	/// Determines what part of a VPK set a file name refers to.
+	/// 
+	/// Names that aren't valid UTF-8 can't be matched against the naming convention so are treated as standalone.
+	pub(super) fn parse_archive_name(name : &std::ffi::OsStr) -> ArchiveName<'_> {
+		let Some(name) = name.to_str() else { return ArchiveName::Standalone };
+		let Some(stem) = name.strip_suffix(".vpk") else { return ArchiveName::Standalone };
+		let Some((base, suffix)) = stem.rsplit_once('_') else { return ArchiveName::Standalone };
+
+		if suffix == "dir" {
+			ArchiveName::Directory(base)
+		} else if suffix.len() == 3 && suffix.bytes().all(|b| b.is_ascii_digit()) {
+			ArchiveName::Chunk(base, suffix.parse().unwrap())
+		} else {
+			ArchiveName::Standalone
+		}
+	}
+
+	/// Finds the directory file and every sequentially numbered data archive belonging to the VPK at `path`.
+	/// 
+	/// # Arguments
+	/// * `path` - Path to the directory file, any of the data archives or a standalone single file VPK.
+	/// 
+	/// # Errors
+	/// * `DoesNotExist` - When `path` or the directory file of its set doesn't exist.
+	/// * `NotVPK` - When `path` doesn't name a file.
+	pub(super) fn resolve_archive_paths(path : &Path) -> Result<(PathBuf, Vec<PathBuf>), ErrorKind> {
+		if !path.is_file() {
+			return Err(ErrorKind::DoesNotExist(path.display().to_string()));
+		}
+		let name = path.file_name().ok_or_else(|| ErrorKind::NotVPK(path.display().to_string()))?;
+
+		let base = match parse_archive_name(name) {
+			ArchiveName::Standalone => return Ok((path.to_path_buf(), Vec::new())),
+			ArchiveName::Directory(base) | ArchiveName::Chunk(base, _) => base,
+		};
+
+		let dir_path = path.with_file_name(format!("{}_dir.vpk", base));
+		if !dir_path.is_file() {
+			return Err(ErrorKind::DoesNotExist(format!("Directory file {} for {}", dir_path.display(), path.display())));
+		}
+
+		let mut data_paths = Vec::<PathBuf>::new();
+		for i in 0..data::DirectoryEntryData::DATA_IN_DIRECTORY_ARCHIVE_INDEX {
+			let p = path.with_file_name(format!("{}_{:0>3}.vpk", base, i));
+			if !p.is_file() {
+				break;
+			}
+			data_paths.push(p);
+		}
+
+		Ok((dir_path, data_paths))
 	}
 	
+	/// Opens the directory file and every sequentially numbered data archive next to it.
+	/// 
+	/// # Arguments
+	/// * `path` - Path to the directory file, any of the data archives or a standalone single file VPK.
+	pub(super) fn open_archive_files(path : &Path) -> Result<(Reader, Vec<Reader>), ErrorKind> {
+		let (dir_path, data_paths) = resolve_archive_paths(path)?;
+		open_files_with(&dir_path, &data_paths, |file| Ok(Arc::new(file)))
+	}
+
+	/// Opens the directory file and data archives, mapping each into memory.
+	/// 
+	/// # Safety
+	/// The files must not be modified while mapped, see `memmap2::Mmap::map`.
+	pub(super) unsafe fn map_archive_files(path : &Path) -> Result<(Reader, Vec<Reader>), ErrorKind> {
+		let (dir_path, data_paths) = resolve_archive_paths(path)?;
+		open_files_with(&dir_path, &data_paths, |file| Ok(Arc::new(memmap2::Mmap::map(&file)?)))
+	}
+
+	/// Opens exactly the given directory file and data archives, regardless of how they are named.
+	pub(super) fn open_files(dir_path : &Path, data_paths : &[PathBuf]) -> Result<(Reader, Vec<Reader>), ErrorKind> {
+		open_files_with(dir_path, data_paths, |file| Ok(Arc::new(file)))
+	}
+
+	fn open_files_with(dir_path : &Path, data_paths : &[PathBuf], into_reader : impl Fn(File) -> Result<Reader, ErrorKind>) -> Result<(Reader, Vec<Reader>), ErrorKind> {
+		let open = |p : &Path| match File::open(p) {
+			Ok(file) => into_reader(file),
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(ErrorKind::DoesNotExist(p.display().to_string())),
+			Err(e) => Err(e.into()),
+		};
+
+		let dir_file = open(dir_path)?;
+		let data_file = data_paths.iter().map(|p| open(p)).collect::<Result<Vec<Reader>, ErrorKind>>()?;
+
+		Ok((dir_file, data_file))
+	}
+
+	/// Compares exactly `length` bytes from two readers in blocks, returning whether they match.
+	///
+	/// `inspect` is given each matching block, e.g. to hash the data while it's compared.
+	pub(super) fn same_data<A : Read + ?Sized, B : Read + ?Sized>(a : &mut A, b : &mut B, length : u64, mut inspect : impl FnMut(&[u8])) -> Result<bool, ErrorKind> {
+		const BLOCK_SIZE : u64 = 64 * 1024;
+		let size = std::cmp::min(length, BLOCK_SIZE).try_into().unwrap();
+		let (mut buf_a, mut buf_b) = (vec![0u8; size], vec![0u8; size]);
+		let mut remaining = length;
+		while remaining > 0 {
+			let len = std::cmp::min(remaining, BLOCK_SIZE).try_into().unwrap();
+			a.read_exact(&mut buf_a[..len])?;
+			b.read_exact(&mut buf_b[..len])?;
+			if buf_a[..len] != buf_b[..len] {
+				return Ok(false);
+			}
+			inspect(&buf_a[..len]);
+			remaining -= len as u64;
+		}
+		Ok(true)
+	}
+
+	/// Reads a `ReadAt` source sequentially from a starting position.
+	pub(super) struct SectionReader<'a> {
+		source : &'a dyn ReadAt,
+		position : u64,
+	}
+
+	impl<'a> SectionReader<'a> {
+		pub(super) fn new(source : &'a dyn ReadAt, position : u64) -> Self {
+			SectionReader { source, position }
+		}
+	}
+
+	impl Read for SectionReader<'_> {
+		fn read(&mut self, buf : &mut [u8]) -> std::io::Result<usize> {
+			let n = self.source.read_at(buf, self.position)?;
+			self.position += u64::try_from(n).unwrap();
+			Ok(n)
+		}
+	}
+
+	/// A glob matched against entry paths, shared by `DirectoryFilter` and pack rules so both match the same paths.
+	#[derive(Clone, Debug)]
+	pub(super) struct PathPattern(glob::Pattern);
+
+	impl PathPattern {
+		const OPTIONS : glob::MatchOptions = glob::MatchOptions { case_sensitive : true, require_literal_separator : false, require_literal_leading_dot : false };
+
+		/// # Errors
+		/// * `MalformedData` - When `pattern` isn't a valid glob.
+		pub(super) fn new(pattern : &str) -> Result<Self, ErrorKind> {
+			glob::Pattern::new(pattern)
+				.map(PathPattern)
+				.map_err(|e| ErrorKind::MalformedData(format!("Pattern \"{}\": {}", pattern, e)))
+		}
+
+		/// Whether `path` matches, `*` also matches across `/` and a leading dot needn't be matched literally.
+		pub(super) fn matches(&self, path : &str) -> bool {
+			self.0.matches_with(path, Self::OPTIONS)
+		}
+	}
+
+	/// Reads `size` bytes of the directory file from `start`, e.g. its header or one of its sections.
+	/// 
+	/// The last byte is read first so that a size past the end of the file fails before anything is allocated for it.
+	/// 
+	/// # Errors
+	/// * `MalformedData` - When the bytes run past the end of the directory file, giving the offset.
+	pub(super) fn read_dir_section(dir : &dyn ReadAt, start : u64, size : u64, name : &str) -> Result<Vec<u8>, ErrorKind> {
+		let past_end = || ErrorKind::MalformedData(format!("{} of {} bytes at offset {} runs past the end of the directory file", name, size, start));
+		let map_eof = |e : std::io::Error| if e.kind() == std::io::ErrorKind::UnexpectedEof { past_end() } else { e.into() };
+
+		if size > 0 {
+			dir.read_exact_at(&mut [0u8], start + size - 1).map_err(map_eof)?;
+		}
+		let mut buf = vec![0u8; usize::try_from(size).map_err(|_| past_end())?];
+		dir.read_exact_at(&mut buf, start).map_err(map_eof)?;
+		Ok(buf)
+	}
+
 	/// Helper function for reading arrays of a type.
 	/// 
 	/// # Arguments
 	/// * `buf` - buffer containing the whole section to be iterated.
-	pub(super) fn read_section<T: Sized>(buf : &[u8]) -> Vec<T> {
-		let entry_size : usize = std::mem::size_of::<T>();
+	/// * `entry_size` - The serialized size of a single `T`.
+	pub(super) fn read_section<T: serde::de::DeserializeOwned>(buf : &[u8], entry_size : usize) -> Result<Vec<T>, ErrorKind> {
 		let mut section = Vec::<T>::new();
-		let mut cursor: usize = 0;
-		
-		loop {
-			if buf.len() <= cursor {
-				break;
+		for chunk in buf.chunks(entry_size) {
+			if chunk.len() != entry_size {
+				return Err(ErrorKind::MalformedData("Section size is not a multiple of its entry size".to_string()));
 			}
-			section.push(
-				crate::resource::read_from_bytes::<T>(&buf[cursor..cursor + entry_size])
-			);
-			cursor += entry_size;
+			section.push(bincode::deserialize::<T>(chunk)?);
 		}
-	
-		section
+		Ok(section)
 	}
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Version {
 	V1,
 	V2,
-	UNKNOWN,
-	NOTVPK,
 }
 
 /// Reads a vpk and tries to determine the version
+/// 
+/// # Errors
+/// * `DoesNotExist` - When the file or the directory file of its set doesn't exist.
+/// * `NotVPK` - When the file does not start with `VPK_SIGNATURE`.
+/// * `UnsupportedVersion` - When the file is a VPK of a version we can't read.
 pub fn determine_version(path : &std::path::Path) -> Result<Version, ErrorKind> {
-	let mut dir_file = std::fs::File::open(helpers::get_base_path(path) + "dir.vpk")?;
+	let (dir_path, _) = helpers::resolve_archive_paths(path)?;
+	let mut dir_file = std::fs::File::open(dir_path)?;
 
 	/* Confirm file is a supported VPK and if so, open it */
 
 	///Common between all VPK formats
+	#[repr(C)]
 	struct CommonHeader {
 		signature : u32,
 		version : u32,
@@ -135,59 +607,147 @@ pub fn determine_version(path : &std::path::Path) -> Result<Version, ErrorKind>
 
 	let mut buf = [0u8; 8];
 	dir_file.seek(std::io::SeekFrom::Start(0))?;
-	dir_file.read_exact(&mut buf)?;
+	dir_file.read_exact(&mut buf).map_err(|e| match e.kind() {
+		std::io::ErrorKind::UnexpectedEof => ErrorKind::MalformedData("Header at offset 0 runs past the end of the directory file".to_string()),
+		_ => e.into(),
+	})?;
 	let h = read_from_bytes::<CommonHeader>(&buf);
 
 	if h.signature != data::VPK_SIGNATURE {
-		return Ok(Version::NOTVPK);
+		return Err(ErrorKind::NotVPK(path.display().to_string()));
 	}
 	
-	Ok(
-		/* TODO: TF2 also uses version 2 but has a sub version so this needs to account for that. */
-		match h.version {
-			1 => Version::V1,
-			2 => Version::V2, 
-			_ => Version::UNKNOWN,
+	/* TODO: TF2 also uses version 2 but has a sub version so this needs to account for that. */
+	match h.version {
+		1 => Ok(Version::V1),
+		2 => Ok(Version::V2),
+		v => Err(ErrorKind::UnsupportedVersion(v)),
+	}
+}
+
+/// A VPK of any supported version.
+pub enum VPK {
+	V1(VPKv1),
+	V2(VPKv2),
+}
+
+impl VPK {
+	pub fn version(&self) -> Version {
+		match self {
+			VPK::V1(_) => Version::V1,
+			VPK::V2(_) => Version::V2,
 		}
-	)
+	}
 }
 
 /// Opens a VPK file for reading.
 /// 
 /// # Arguments
-/// * `path` - The path to the VPK.
+/// * `path` - The path to the VPK, either the `_dir.vpk`, one of its numbered data archives or a standalone single file VPK such as `addon.vpk`.
 /// 
 /// # Errors
-/// * `FileError` - When a problem is encountered with the file io, these are progated and so could be a wide range of io errors.
-/// * `InvalidHeader` - When the header is malformed.
-// pub fn open(path : &std::path::Path) -> Result<VPK<R>, Error> {
-// 	let dir_file = std::fs::File::open(helpers::get_base_path(path) + "dir.vpk")?;
-
-// 	/* Confirm file is a supported VPK and if so, open it */
-
-// 	///Common between all VPK formats
-// 	struct CommonHeader {
-// 		signature : u32,
-// 		version : u32,
-// 	}
-
-// 	let mut buf = [0u8; 8];
-// 	dir_file.seek(std::io::SeekFrom::Start(0))?;
-// 	dir_file.read_exact(&mut buf)?;
-// 	let h = read_from_bytes::<CommonHeader>(&buf);
-
-// 	if h.signature != data::VPK_SIGNATURE {
-// 		return Err(Error::InvalidHeader("signature does not match"));
-// 	}
-
-// 	Ok(
-// 		match h.version {
-// 			1 => todo!(), //VPK::V1(v1::VPKv1::open(path)?),
-// 			2 => VPK::V2(v2::VPKv2::open_from_path(path)?), /* TODO: TF2 also uses version 2 but has a sub version so this needs to account for that. */
-// 			_ => return Err(Error::InvalidHeader("unsupported version")),
-// 		}
-// 	)
-// }
+/// * `DoesNotExist` - When the file or the directory file of its set doesn't exist.
+/// * `IO` - When a problem is encountered with the file io, these are progated and so could be a wide range of io errors.
+/// * `NotVPK` - When the file is not a VPK.
+/// * `UnsupportedVersion` - When the file is a VPK of a version we can't read.
+/// * `MalformedData` - When the header or directory is malformed.
+pub fn open(path : &Path) -> Result<VPK, ErrorKind> {
+	Ok(
+		match determine_version(path)? {
+			Version::V1 => VPK::V1(VPKv1::open_from_path(path)?),
+			Version::V2 => VPK::V2(VPKv2::open_from_path(path)?),
+		}
+	)
+}
+
+impl Open for VPK {
+	fn open_from_path(path : &Path) -> Result<Self, ErrorKind> {
+		open(path)
+	}
+}
+
+impl Extract for VPK {
+	type EntryReader = directory::EntryReader;
+
+	fn get_entry_from_path(&self, path : &str) -> Result<Self::EntryReader, ErrorKind> {
+		match self {
+			VPK::V1(vpk) => vpk.get_entry_from_path(path),
+			VPK::V2(vpk) => vpk.get_entry_from_path(path),
+		}
+	}
+}
+
+impl List for VPK {
+	fn get_handles(&self) -> Vec<&EntryHandle> {
+		match self {
+			VPK::V1(vpk) => vpk.get_handles(),
+			VPK::V2(vpk) => vpk.get_handles(),
+		}
+	}
+
+	fn get_handle_from_path(&self, path : &str) -> Option<&EntryHandle> {
+		match self {
+			VPK::V1(vpk) => vpk.get_handle_from_path(path),
+			VPK::V2(vpk) => vpk.get_handle_from_path(path),
+		}
+	}
+}
+
+impl ValidateArchive for VPK {
+	type Checksum = data::ArchiveMD5SectionEntry;
+
+	/// V1 has no archive checksums so always passes.
+	fn validate_archive(&self) -> Vec<&Self::Checksum> {
+		match self {
+			VPK::V1(_) => Vec::new(),
+			VPK::V2(vpk) => vpk.validate_archive(),
+		}
+	}
+}
+
+impl ValidateEntries for VPK {
+	fn validate_entry(&self, path : &str) -> Result<bool, ErrorKind> {
+		match self {
+			VPK::V1(vpk) => vpk.validate_entry(path),
+			VPK::V2(vpk) => vpk.validate_entry(path),
+		}
+	}
+
+	fn validate_entries(&self) -> Result<Vec<&str>, ErrorKind> {
+		match self {
+			VPK::V1(vpk) => vpk.validate_entries(),
+			VPK::V2(vpk) => vpk.validate_entries(),
+		}
+	}
+}
+
+impl ValidateOther for VPK {
+	/// V1 has no other checksums so always passes.
+	fn validate_other(&self) -> Result<(), ErrorKind> {
+		match self {
+			VPK::V1(_) => Ok(()),
+			VPK::V2(vpk) => vpk.validate_other(),
+		}
+	}
+}
+
+impl ValidateSignature for VPK {
+	/// V1 cannot be signed so always returns `DoesNotExist`.
+	fn validate_signature(&self) -> Result<(), ErrorKind> {
+		match self {
+			VPK::V1(_) => Err(ErrorKind::DoesNotExist("Signature".to_string())),
+			VPK::V2(vpk) => vpk.validate_signature(),
+		}
+	}
+
+	/// V1 cannot be signed so always returns `DoesNotExist`.
+	fn validate_signature_with_key(&self, public_key : &[u8]) -> Result<(), ErrorKind> {
+		match self {
+			VPK::V1(_) => Err(ErrorKind::DoesNotExist("Signature".to_string())),
+			VPK::V2(vpk) => vpk.validate_signature_with_key(public_key),
+		}
+	}
+}
 
 #[cfg(test)]
 mod tests {
@@ -195,11 +755,51 @@ mod tests {
 	use super::*;
 
 	#[test]
-	fn get_base_path() {
-		assert_eq!(super::helpers::get_base_path(std::path::Path::new("/example/directory/file_dir.vpk")), "/example/directory/file_");
-		assert_eq!(super::helpers::get_base_path(std::path::Path::new("/example/directory/file_001.vpk")), "/example/directory/file_");
+	fn parse_archive_name() {
+		use std::ffi::OsStr;
+		use super::helpers::{parse_archive_name, ArchiveName};
+
+		assert_eq!(parse_archive_name(OsStr::new("file_dir.vpk")), ArchiveName::Directory("file"));
+		assert_eq!(parse_archive_name(OsStr::new("file_001.vpk")), ArchiveName::Chunk("file", 1));
+		assert_eq!(parse_archive_name(OsStr::new("my_mod_dir.vpk")), ArchiveName::Directory("my_mod"));
+		assert_eq!(parse_archive_name(OsStr::new("addon.vpk")), ArchiveName::Standalone);
+		assert_eq!(parse_archive_name(OsStr::new("my_mod.vpk")), ArchiveName::Standalone);
+		assert_eq!(parse_archive_name(OsStr::new("file_1.vpk")), ArchiveName::Standalone);
+		assert_eq!(parse_archive_name(OsStr::new("dir.vpk")), ArchiveName::Standalone);
+		assert_eq!(parse_archive_name(OsStr::new("a")), ArchiveName::Standalone);
+		assert_eq!(parse_archive_name(OsStr::new("")), ArchiveName::Standalone);
+		#[cfg(unix)] {
+			use std::os::unix::ffi::OsStrExt;
+			assert_eq!(parse_archive_name(OsStr::from_bytes(b"\xff_dir.vpk")), ArchiveName::Standalone);
+		}
 	}
 
-	/* TODO: `open` test */
+	#[test]
+	fn split_entry_path() {
+		use super::directory::{split_entry_path, join_entry_path};
+
+		let split = |p : &str| {
+			let (path, filename, extension) = split_entry_path(p);
+			assert_eq!(join_entry_path(&path, &filename, &extension), p.replace('\\', "/").trim_start_matches('/'));
+			(path, filename, extension)
+		};
+		let owned = |(a, b, c) : (&str, &str, &str)| (a.to_string(), b.to_string(), c.to_string());
+
+		assert_eq!(split("materials/models/example.vmt"), owned(("materials/models", "example", "vmt")));
+		assert_eq!(split("addoninfo.txt"), owned((" ", "addoninfo", "txt")));
+		assert_eq!(split("bin/README"), owned(("bin", "README", " ")));
+		assert_eq!(split("README"), owned((" ", "README", " ")));
+		assert_eq!(split("scripts/.gitignore"), owned(("scripts", ".gitignore", " ")));
+		assert_eq!(split("a.b/c.d.e"), owned(("a.b", "c.d", "e")));
+		assert_eq!(split("\\sound\\example.wav"), owned(("sound", "example", "wav")));
+	}
+
+	#[test]
+	fn open() {
+		let path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR").to_owned() + "/test-data/vpk_v1_test_dir.vpk");
+		assert_eq!(super::open(&path).unwrap().version(), Version::V1);
+		let path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR").to_owned() + "/test-data/vpk_test_dir.vpk");
+		assert_eq!(super::open(&path).unwrap().version(), Version::V2);
+	}
 }
 
@@ -0,0 +1,143 @@
+//! Comparing the entries of two VPKs, e.g. before and after a game update.
+
+use serde::{Serialize, Deserialize};
+use super::*;
+
+/// Settings used when comparing VPKs.
+#[derive(Clone, Debug, Default)]
+pub struct DiffOptions {
+	/// Compares the data of entries with the same size byte for byte rather than by CRC.
+	///
+	/// This reads every entry in both VPKs but doesn't trust CRCs which may be wrong. Entries without a CRC are always compared by data.
+	pub compare_data : bool,
+}
+
+/// How an entry differs between two VPKs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ChangeKind {
+	/// Only in the new VPK.
+	Added,
+	/// Only in the old VPK.
+	Removed,
+	/// In both with different data.
+	Modified,
+	/// In both with the same data stored in a different data archive, or moved between a data archive and the directory file.
+	///
+	/// Entries which only moved within the same file or changed their preload size aren't moved, nor are preload only entries.
+	Moved,
+}
+
+/// Where and how an entry is stored.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntryLocation {
+	pub crc : u32,
+	/// Total size including the preload.
+	pub size : u32,
+	pub preload_size : u16,
+	/// Index of the data archive, `0x7fff` when embedded in the directory file.
+	pub archive_index : u16,
+	pub offset : u32,
+}
+
+impl EntryLocation {
+	fn new(handle : &EntryHandle) -> Self {
+		EntryLocation {
+			crc : handle.crc(),
+			size : handle.total_size(),
+			preload_size : handle.preload_size(),
+			archive_index : handle.archive_index(),
+			offset : handle.offset(),
+		}
+	}
+
+	fn is_preload_only(&self) -> bool {
+		self.size == u32::from(self.preload_size)
+	}
+}
+
+/// An entry which differs between two VPKs.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntryChange {
+	pub path : String,
+	pub kind : ChangeKind,
+	/// The entry in the old VPK, `None` when added.
+	pub old : Option<EntryLocation>,
+	/// The entry in the new VPK, `None` when removed.
+	pub new : Option<EntryLocation>,
+}
+
+/// The differences between two VPKs, see `diff`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diff {
+	/// Every entry which differs, sorted by path.
+	pub changes : Vec<EntryChange>,
+	/// The number of entries in both with the same data in the same file.
+	pub unchanged : usize,
+}
+
+impl Diff {
+	/// The number of changes of the given kind.
+	pub fn count(&self, kind : ChangeKind) -> usize {
+		self.changes.iter().filter(|c| c.kind == kind).count()
+	}
+
+	/// Whether the VPKs hold the same entries with the same data in the same files.
+	pub fn is_empty(&self) -> bool {
+		self.changes.is_empty()
+	}
+
+	/// Serializes the differences as pretty printed JSON.
+	pub fn to_json(&self) -> String {
+		serde_json::to_string_pretty(self).expect("Diff can't be serialized")
+	}
+}
+
+/// Compares the entries of two VPKs of any version by path.
+///
+/// Entries in both are modified if their size or CRC differ, or their data with `DiffOptions::compare_data`.
+/// A CRC of 0 means the entry was written without one, so when either is 0 the data is compared instead.
+/// Otherwise they're moved if their data archive differs, unless either is preload only.
+///
+/// # Errors
+/// * `IO` - When the data of an entry can't be read to be compared.
+pub fn diff<A : List + Extract, B : List + Extract>(old : &A, new : &B, options : &DiffOptions) -> Result<Diff, ErrorKind> {
+	let mut result = Diff::default();
+
+	for old_handle in old.get_handles() {
+		let path = old_handle.path();
+		let old_location = EntryLocation::new(old_handle);
+		let Some(new_handle) = new.get_handle_from_path(path) else {
+			result.changes.push(EntryChange { path : path.to_string(), kind : ChangeKind::Removed, old : Some(old_location), new : None });
+			continue;
+		};
+		let new_location = EntryLocation::new(new_handle);
+
+		let same = if old_location.size != new_location.size {
+			false
+		} else if options.compare_data || old_location.crc == 0 || new_location.crc == 0 {
+			let (mut old_reader, mut new_reader) = (old.get_entry_from_path(path)?, new.get_entry_from_path(path)?);
+			helpers::same_data(&mut old_reader, &mut new_reader, old_location.size.into(), |_| {})?
+		} else {
+			old_location.crc == new_location.crc
+		};
+
+		let kind = if !same {
+			ChangeKind::Modified
+		} else if old_location.archive_index != new_location.archive_index && !old_location.is_preload_only() && !new_location.is_preload_only() {
+			ChangeKind::Moved
+		} else {
+			result.unchanged += 1;
+			continue;
+		};
+		result.changes.push(EntryChange { path : path.to_string(), kind, old : Some(old_location), new : Some(new_location) });
+	}
+
+	for new_handle in new.get_handles() {
+		if old.get_handle_from_path(new_handle.path()).is_none() {
+			result.changes.push(EntryChange { path : new_handle.path().to_string(), kind : ChangeKind::Added, old : None, new : Some(EntryLocation::new(new_handle)) });
+		}
+	}
+
+	result.changes.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+	Ok(result)
+}
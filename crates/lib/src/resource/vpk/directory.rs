@@ -0,0 +1,419 @@
+use std::convert::TryInto;
+use std::sync::Arc;
+
+use crate::resource::error::ErrorKind;
+use super::data::DirectoryEntryData;
+use super::Reader;
+
+/// Valve's placeholder for an empty path or extension in the directory tree, as an empty string would end the list.
+pub(super) const EMPTY_COMPONENT : &str = " ";
+
+/// Joins the components stored in the directory tree into an entry's full path, leaving out placeholders.
+/// 
+/// e.g. `("materials", "example", "vmt")` -> `materials/example.vmt`, `(" ", "addoninfo", "txt")` -> `addoninfo.txt`
+pub(super) fn join_entry_path(path : &str, filename : &str, extension : &str) -> String {
+	let mut full = String::with_capacity(path.len() + filename.len() + extension.len() + 2);
+	if path != EMPTY_COMPONENT {
+		full.push_str(path);
+		full.push('/');
+	}
+	full.push_str(filename);
+	if extension != EMPTY_COMPONENT {
+		full.push('.');
+		full.push_str(extension);
+	}
+	full
+}
+
+/// Splits a full path into the path, filename and extension stored in the directory tree.
+/// 
+/// Backslashes are treated as separators and a missing path or extension is replaced by `EMPTY_COMPONENT`.
+/// A leading dot is part of the filename, so `.gitignore` has no extension.
+pub(super) fn split_entry_path(full : &str) -> (String, String, String) {
+	let full = full.replace('\\', "/");
+	let full = full.trim_start_matches('/');
+
+	let (path, name) = full.rsplit_once('/').unwrap_or(("", full));
+	let (filename, extension) = match name.rsplit_once('.') {
+		Some((filename, extension)) if !filename.is_empty() => (filename, extension),
+		_ => (name, ""),
+	};
+
+	let placeholder = |s : &str| if s.is_empty() { EMPTY_COMPONENT.to_string() } else { s.to_string() };
+	(placeholder(path), filename.to_string(), placeholder(extension))
+}
+
+#[derive(Default, Clone)]
+pub(super) struct Directory {
+	pub(super) entries : Vec<Arc<Handle>>,
+	pub(super) map : std::collections::HashMap<String, Arc<Handle>>,
+}
+
+/// Reads the standard directory tree seen in most VPK formats.
+/// 
+/// # Arguments
+/// * `input` - The bytes begining at the tree's offset and ending at tree offset + length.
+/// * `offset` - Used to determine `preload_data_position` relative to the start of the VPK.
+/// * `directory_archive_offset` - Used to determine `preload_data_position` relative to the start of the VPK.
+/// 
+/// # Errors
+/// * `MalformedData` - When a string, entry or preload data runs past the end of the tree, or an entry is invalid.
+///   The message includes the offset from the start of the VPK.
+pub(super) fn read_directory_tree(input: &[u8], offset : u64, directory_archive_offset : u64) -> Result<Directory, ErrorKind> {
+	let malformed = |what : &str, cursor : u64| ErrorKind::MalformedData(format!("{} at offset {}", what, offset + cursor));
+
+	let read_string = |cursor : &mut u64| -> Result<String, ErrorKind> {
+		let start = usize::try_from(*cursor).unwrap();
+		let length = input.get(start..).and_then(|rest| rest.iter().position(|&c| c == 0))
+			.ok_or_else(|| malformed("Unterminated string in directory tree", *cursor))?;
+		*cursor += u64::try_from(length + 1).unwrap();
+		Ok(input[start..start + length].iter().map(|&c| char::from(c)).collect())
+	};
+	
+	let mut directory = Directory::default();
+	let mut cursor: u64 = 0;
+	loop {
+		let extension = read_string(&mut cursor)?;
+		if extension.is_empty() { break; }
+		loop {
+			let path = read_string(&mut cursor)?;
+			if path.is_empty() { break; }
+			loop {
+				let filename = read_string(&mut cursor)?;
+				if filename.is_empty() { break; }
+				
+				let start = usize::try_from(cursor).unwrap();
+				let buf = input.get(start..start + DirectoryEntryData::SIZE)
+					.ok_or_else(|| malformed("Directory entry past the end of the tree", cursor))?;
+				let entry = bincode::deserialize::<DirectoryEntryData>(buf)?;
+
+				entry.is_valid().map_err(|e| match e {
+					ErrorKind::MalformedData(what) => malformed(&what, cursor),
+					e => e,
+				})?;
+
+				cursor += u64::try_from(DirectoryEntryData::SIZE).unwrap();
+
+				if cursor + u64::from(entry.preload_bytes_size) > u64::try_from(input.len()).unwrap() {
+					return Err(malformed("Preload data past the end of the tree", cursor));
+				}
+
+				let handle = Arc::new(Handle {
+					path : join_entry_path(&path, &filename, &extension),
+					directory_len : if path == EMPTY_COMPONENT { 0 } else { path.len() },
+					extension_len : if extension == EMPTY_COMPONENT { 0 } else { extension.len() },
+					entry,
+					preload_data_position: offset + cursor,
+					directory_archive_data_start_position: directory_archive_offset,
+				});
+				
+				cursor += u64::from(handle.entry.preload_bytes_size);
+				
+				directory.entries.push(handle.clone());
+				directory.map.insert(handle.path.clone(), handle.clone());
+			}
+		}
+	}
+
+	Ok(directory)
+}
+
+impl Directory {
+	pub(super) fn get_handles(&self) -> Vec<&Handle> {
+		self.entries.iter().map(|h| h.as_ref()).collect()
+	}
+
+	pub(super) fn get_handle_from_path(&self, path : &str) -> Option<&Handle> {
+		self.map.get(path).map(|h| h.as_ref())
+	}
+
+	/// Creates a reader for the entry at `path`.
+	/// 
+	/// # Arguments
+	/// * `path` - The full path of the entry, e.g. `materials/example.vmt`.
+	/// * `dir` - The file containing the directory tree, preload data and embedded archive.
+	/// * `data` - The indexed data archives.
+	pub(super) fn get_entry_reader(&self, path : &str, dir : &Reader, data : &[Reader]) -> Result<EntryReader, ErrorKind> {
+		let handle = self.map.get(path).ok_or_else(|| ErrorKind::DoesNotExist(path.to_string()))?;
+		let data = if handle.entry.is_in_directory_archive() || handle.entry.is_preload_only() {
+			None
+		} else {
+			let archive = data.get(usize::from(handle.entry.archive_index))
+				.ok_or_else(|| ErrorKind::DoesNotExist(format!("Archive {} for entry {}", handle.entry.archive_index, path)))?;
+			Some(archive.clone())
+		};
+		Ok(EntryReader::new((**handle).clone(), dir.clone(), data))
+	}
+
+	/// Borrows the data of the entry at `path` directly from its sources.
+	/// 
+	/// # Errors
+	/// * `Unsupported` - When the sources are not held in memory.
+	/// * `MalformedData` - When the entry lies outside of its sources.
+	pub(super) fn get_entry_bytes<'a>(&self, path : &str, dir : &'a Reader, data : &'a [Reader]) -> Result<EntryBytes<'a>, ErrorKind> {
+		fn slice<'a>(source : &'a [u8], start : u64, length : u64, path : &str) -> Result<&'a [u8], ErrorKind> {
+			usize::try_from(start).ok()
+				.zip(usize::try_from(start + length).ok())
+				.and_then(|(start, end)| source.get(start..end))
+				.ok_or_else(|| ErrorKind::MalformedData(format!("Data for {} is outside of its archive", path)))
+		}
+
+		let handle = self.map.get(path).ok_or_else(|| ErrorKind::DoesNotExist(path.to_string()))?;
+		let dir_bytes = dir.as_bytes().ok_or_else(|| ErrorKind::Unsupported("Directory file is not held in memory".to_string()))?;
+
+		let preload = slice(dir_bytes, handle.preload_data_position, handle.entry.preload_bytes_size.into(), path)?;
+
+		let archive = if handle.entry.is_preload_only() {
+			&[]
+		} else if handle.entry.is_in_directory_archive() {
+			slice(dir_bytes, handle.directory_archive_data_start_position + u64::from(handle.entry.data_offset), handle.entry.data_length.into(), path)?
+		} else {
+			let archive = data.get(usize::from(handle.entry.archive_index))
+				.ok_or_else(|| ErrorKind::DoesNotExist(format!("Archive {} for entry {}", handle.entry.archive_index, path)))?;
+			let archive_bytes = archive.as_bytes().ok_or_else(|| ErrorKind::Unsupported(format!("Archive {} is not held in memory", handle.entry.archive_index)))?;
+			slice(archive_bytes, handle.entry.data_offset.into(), handle.entry.data_length.into(), path)?
+		};
+
+		Ok(EntryBytes { preload, archive })
+	}
+
+	/// Reads the whole entry at `path` and checks it against its CRC.
+	/// 
+	/// # Returns
+	/// `true` if the data matches the CRC.
+	pub(super) fn validate_entry(&self, path : &str, dir : &Reader, data : &[Reader]) -> Result<bool, ErrorKind> {
+		use std::io::Read;
+
+		let mut reader = self.get_entry_reader(path, dir, data)?;
+		let mut crc = crc32fast::Hasher::new();
+		let mut buf = vec![0u8; 64 * 1024];
+		loop {
+			let n = reader.read(&mut buf)?;
+			if n == 0 { break; }
+			crc.update(&buf[..n]);
+		}
+		Ok(crc.finalize() == reader.handle.entry.crc)
+	}
+
+	/// Checks every entry against its CRC.
+	/// 
+	/// # Returns
+	/// The paths of all entries that failed.
+	pub(super) fn validate_entries(&self, dir : &Reader, data : &[Reader]) -> Result<Vec<&str>, ErrorKind> {
+		let mut failed = Vec::<&str>::new();
+		for handle in &self.entries {
+			if !self.validate_entry(&handle.path, dir, data)? {
+				failed.push(&handle.path);
+			}
+		}
+		Ok(failed)
+	}
+}
+
+#[derive(Clone)]
+pub struct Handle {
+	/// The full path of the entry, e.g. `materials/example.vmt`.
+	pub(super) path : String,
+	/// Length of the path component at the start of `path`, 0 when it was a placeholder.
+	directory_len : usize,
+	/// Length of the extension component at the end of `path`, 0 when it was a placeholder.
+	extension_len : usize,
+	pub(super) entry : DirectoryEntryData,
+	pub(super) preload_data_position : u64,
+	/// Where the embedded archive data is located in the VPK.
+	pub(super) directory_archive_data_start_position : u64,
+}
+
+impl Handle {
+	/// The full path of the entry, e.g. `materials/example.vmt`.
+	pub fn path(&self) -> &str { &self.path }
+	/// The CRC32 of the entry's complete data as stored in the directory.
+	pub fn crc(&self) -> u32 { self.entry.crc }
+	/// The size of the entry's complete data, preload and archive combined.
+	pub fn total_size(&self) -> u32 { self.entry.total_data_size() }
+	/// How many bytes of the entry are stored as preload data in the directory tree.
+	pub fn preload_size(&self) -> u16 { self.entry.preload_bytes_size }
+	/// How many bytes of the entry are stored in an archive.
+	pub fn archive_size(&self) -> u32 { self.entry.data_length }
+	/// The index of the data archive containing the entry, `0x7fff` when embedded in the directory file.
+	pub fn archive_index(&self) -> u16 { self.entry.archive_index }
+	/// Offset of the entry's archive data, relative to the start of the embedded archive data when embedded.
+	pub fn offset(&self) -> u32 { self.entry.data_offset }
+	/// If the entry's archive data is embedded in the directory file rather than a data archive.
+	pub fn is_embedded(&self) -> bool { self.entry.is_in_directory_archive() }
+	/// If the entry is entirely stored as preload data.
+	pub fn is_preload_only(&self) -> bool { self.entry.is_preload_only() }
+
+	/// The path, filename and extension exactly as stored in the directory tree, including placeholders.
+	pub(super) fn components(&self) -> (&str, &str, &str) {
+		let (directory, name) = match self.directory_len {
+			0 => (EMPTY_COMPONENT, self.path.as_str()),
+			n => (&self.path[..n], &self.path[n + 1..]),
+		};
+		let (filename, extension) = match self.extension_len {
+			0 => (name, EMPTY_COMPONENT),
+			n => (&name[..name.len() - n - 1], &name[name.len() - n..]),
+		};
+		(directory, filename, extension)
+	}
+}
+
+/// An entry's data borrowed from a VPK held in memory.
+/// 
+/// The preload and archive data are stored apart so are given separately.
+pub struct EntryBytes<'a> {
+	pub preload : &'a [u8],
+	pub archive : &'a [u8],
+}
+
+impl<'a> EntryBytes<'a> {
+	/// Joins the preload and archive data, only copying if both are present.
+	pub fn join(&self) -> std::borrow::Cow<'a, [u8]> {
+		if self.preload.is_empty() {
+			std::borrow::Cow::Borrowed(self.archive)
+		} else if self.archive.is_empty() {
+			std::borrow::Cow::Borrowed(self.preload)
+		} else {
+			std::borrow::Cow::Owned([self.preload, self.archive].concat())
+		}
+	}
+}
+
+pub struct EntryReader {
+	handle : Handle,
+	/// The VPK directory file.
+	dir : Reader,
+	/// The data file in which the archive data resides. might not exist if the data is embedded in `dir`
+	data : Option<Reader>,
+	cursor : u32,
+	/// Running CRC of the data read so far, only present when checking is enabled.
+	crc : Option<crc32fast::Hasher>,
+	/// How much of the entry has been included in `crc`.
+	crc_position : u32,
+}
+
+impl EntryReader {
+	/// # Panics
+	/// If `data` is `None` when the entry has data in an external archive
+	pub(super) fn new(handle : Handle, dir : Reader, data : Option<Reader>) -> EntryReader {
+		if !handle.entry.is_in_directory_archive() && !handle.entry.is_preload_only() && data.is_none() { panic!("Entry data is external but no external reader given") }
+		EntryReader {
+			dir,
+			data,
+			handle,
+			cursor : 0,
+			crc : None,
+			crc_position : 0,
+		}
+	}
+
+	/// Checks the data read against the entry's CRC, the read reaching the end of the entry fails with `InvalidData` on a mismatch.
+	/// 
+	/// Only data read sequentially from the start is checked, seeking elsewhere disables the check.
+	pub fn with_crc_check(mut self) -> Self {
+		if self.cursor == 0 {
+			self.crc = Some(crc32fast::Hasher::new());
+			self.crc_position = 0;
+		}
+		self
+	}
+}
+
+impl std::io::Seek for EntryReader {
+	fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+		match pos {
+			std::io::SeekFrom::Start(c) => {
+				let pos = std::cmp::min(self.handle.entry.total_data_size(), c.try_into().unwrap_or(u32::MAX));
+				self.cursor = pos;
+			},
+			std::io::SeekFrom::End(c) => {
+				let c : u32 = c.clamp(0, self.handle.entry.total_data_size().into()).try_into().unwrap();
+				let pos : u32 = self.handle.entry.total_data_size() - c;
+				self.cursor = pos;
+			},
+			std::io::SeekFrom::Current(c) => {
+				let pos = (i64::from(self.cursor) + c).clamp(0,self.handle.entry.total_data_size().into());
+				self.cursor = pos.try_into().unwrap();
+			},
+		}
+		Ok(self.cursor.into())
+	}
+}
+
+impl std::io::Read for EntryReader {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		let start = self.cursor;
+		let bytes_read = self.read_entry_data(buf)?;
+
+		if let Some(crc) = self.crc.as_mut() {
+			if start != self.crc_position {
+				self.crc = None;
+			} else {
+				crc.update(&buf[..bytes_read]);
+				self.crc_position += u32::try_from(bytes_read).unwrap();
+				if self.crc_position == self.handle.entry.total_data_size() {
+					let crc = self.crc.take().unwrap().finalize();
+					if crc != self.handle.entry.crc {
+						return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("CRC mismatch for entry {}", self.handle.path)));
+					}
+				}
+			}
+		}
+
+		Ok(bytes_read)
+	}
+}
+
+impl EntryReader {
+	/// Reads entry data into `buf` from the current cursor position.
+	fn read_entry_data(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		use std::io::Seek;
+
+		let mut bytes_read : usize = 0;
+
+		if self.cursor >= self.handle.entry.total_data_size() { return Ok(0); } /* EOF */
+
+		let buf_data = if self.handle.entry.has_preload() && self.cursor < self.handle.entry.preload_bytes_size.into() {
+			let pos = self.handle.preload_data_position + u64::from(self.cursor);
+
+			let remaining_preload_bytes : usize = (u32::from(self.handle.entry.preload_bytes_size) - self.cursor).try_into().unwrap();
+			let (preload, data) = buf.split_at_mut(std::cmp::min(remaining_preload_bytes, buf.len()));
+			self.dir.read_exact_at(preload, pos)?;
+			bytes_read += preload.len();
+			self.seek(std::io::SeekFrom::Current(bytes_read.try_into().unwrap()))?;
+
+			data
+		} else {
+			buf
+		};
+
+		if buf_data.is_empty() || self.cursor >= self.handle.entry.total_data_size() {
+			return Ok(bytes_read);
+		}
+
+		/* We don't need to check the cursor position past here because we've eliminated other conditions */
+
+		{
+			/* Find the real position and get the reader */
+			let cursor_offset_into_archive_entry_data = u64::from(self.cursor - u32::from(self.handle.entry.preload_bytes_size));
+			let (reader, pos) = if self.handle.entry.is_in_directory_archive() {
+				let pos = self.handle.directory_archive_data_start_position + u64::from(self.handle.entry.data_offset) + cursor_offset_into_archive_entry_data;
+				(&self.dir, pos)
+			} else {
+				let pos = u64::from(self.handle.entry.data_offset) + cursor_offset_into_archive_entry_data;
+				(self.data.as_ref().unwrap(), pos)
+			};
+
+			let remaining_data : usize = u64::from(
+				self.handle.entry.data_length - (self.cursor - u32::from(self.handle.entry.preload_bytes_size))
+			).try_into().unwrap();
+			
+			let (data, _rest) = buf_data.split_at_mut(std::cmp::min(remaining_data, buf_data.len()));
+			reader.read_exact_at(data, pos)?;
+			bytes_read += data.len();
+			self.seek(std::io::SeekFrom::Current(data.len().try_into().unwrap()))?;
+		}
+
+		Ok(bytes_read)
+	}
+}
\ No newline at end of file
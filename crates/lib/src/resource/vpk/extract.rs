@@ -0,0 +1,94 @@
+//! Unpacking the entries of a VPK to a folder on disk.
+
+use std::path::Component;
+use super::*;
+
+/// How far `ExtractAll::extract_all` has got, given to its callback after each entry is written.
+#[derive(Clone, Debug)]
+pub struct ExtractProgress<'a> {
+	/// Full path of the entry just written.
+	pub path : &'a str,
+	/// The number of entries written so far, including this one.
+	pub entries_done : usize,
+	/// The number of entries selected for extraction.
+	pub entries_total : usize,
+	/// Bytes of entry data written so far, including this entry.
+	pub bytes_done : u64,
+	/// Total size of the entries selected for extraction.
+	pub bytes_total : u64,
+}
+
+/// The entries written by `ExtractAll::extract_all`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExtractSummary {
+	pub entries : usize,
+	pub bytes : u64,
+	/// Set when the progress callback stopped extraction before every selected entry was written.
+	pub cancelled : bool,
+}
+
+/// This trait allows for VPK formats to unpack their entries to the filesystem.
+pub trait ExtractAll {
+	/// Writes every entry selected by `filter` to a file under `target`, recreating the folder structure.
+	///
+	/// Entries are read in archive and offset order so each data archive is read once from start to end.
+	/// Existing files are overwritten.
+	///
+	/// # Arguments
+	/// * `target` - The folder to extract to, created if it doesn't exist.
+	/// * `filter` - Selects entries by their full path.
+	/// * `progress` - Called after each entry is written, returning `false` cancels before the next entry.
+	///
+	/// # Errors
+	/// * `MalformedData` - When a filter pattern is invalid or an entry's path leads outside of `target`, nothing is written in this case.
+	fn extract_all<F : FnMut(&ExtractProgress) -> bool>(&self, target : &Path, filter : &DirectoryFilter, progress : F) -> Result<ExtractSummary, ErrorKind>;
+}
+
+impl<T : List + Extract> ExtractAll for T {
+	fn extract_all<F : FnMut(&ExtractProgress) -> bool>(&self, target : &Path, filter : &DirectoryFilter, mut progress : F) -> Result<ExtractSummary, ErrorKind> {
+		let matches = filter.compile()?;
+		let mut handles : Vec<&EntryHandle> = self.get_handles().into_iter().filter(|h| matches(h.path())).collect();
+		handles.sort_by_key(|h| (h.archive_index(), h.offset()));
+
+		/* Check every path before anything is written */
+		let destinations = handles.iter().map(|h| entry_destination(target, h.path())).collect::<Result<Vec<_>, _>>()?;
+		let bytes_total = handles.iter().map(|h| u64::from(h.total_size())).sum();
+
+		std::fs::create_dir_all(target)?;
+		let mut summary = ExtractSummary::default();
+		for (handle, destination) in handles.iter().zip(destinations) {
+			if let Some(parent) = destination.parent() {
+				std::fs::create_dir_all(parent)?;
+			}
+			let mut reader = self.get_entry_from_path(handle.path())?;
+			let mut file = File::create(&destination)?;
+			summary.bytes += std::io::copy(&mut reader, &mut file)?;
+			summary.entries += 1;
+
+			let next = progress(&ExtractProgress {
+				path : handle.path(),
+				entries_done : summary.entries,
+				entries_total : handles.len(),
+				bytes_done : summary.bytes,
+				bytes_total,
+			});
+			if !next && summary.entries < handles.len() {
+				summary.cancelled = true;
+				break;
+			}
+		}
+		Ok(summary)
+	}
+}
+
+/// Where the entry at `path` is written under `target`.
+///
+/// # Errors
+/// * `MalformedData` - When the path is absolute or has a parent or prefix component which could lead outside of `target`.
+fn entry_destination(target : &Path, path : &str) -> Result<PathBuf, ErrorKind> {
+	let relative = Path::new(path);
+	if path.is_empty() || !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+		return Err(ErrorKind::MalformedData(format!("Entry path \"{}\" can't be extracted", path)));
+	}
+	Ok(target.join(relative))
+}
@@ -1,113 +1,148 @@
-// use std::{fs::File, path::Path};
-// use super::{EntryDirectorySection, VPKOpen, };
-
-// /// Pure data types used to read file sections.
-// mod data {
-// 	#[repr(C, packed)]
-// 	pub struct HeaderV1 {
-// 		pub signature : u32, /* Should remain const VPK_SIGNATURE*/
-// 		pub version : u32,   /* Should remain const 1 */
-// 		pub tree_size : u32, /* The size, in bytes, of the directory tree */
-// 	}
-// 	pub const HEADER_SIZE : usize = std::mem::size_of::<HeaderV1>();
-// }
-
-
-
-// ///Used in the following titles
-// /// - Alien Swarm
-// /// - Dota 2
-// /// - Left 4 Dead
-// /// - Left 4 Dead 2
-// /// - Portal 2
-// /// - Source Filmmaker
-
-// pub struct VPKv1 {
-// 	directory : super::directory::Directory<>,
-// 	dir_file : File,
-// 	data_file : Vec<File>,
-// }
-
-// impl VPKOpen for VPKv1 {
-// 	fn open(path : &Path) -> Result<Self, crate::resource::error::Error> {
-// 		/* Open files */ 
-// 		let (dir_file, data_file) = {
-// 			let base_path = super::helpers::get_base_path(path);
-			
-// 			let dir_file = File::open(base_path.clone() + "dir.vpk")?;
-			
-// 			let mut data_file = Vec::<File>::new();
-// 			let mut i = 0;
-// 			loop {
-// 				let s = base_path.clone() + &format!("{:0>3}", i).to_string() + ".vpk";
-// 				let p = std::path::Path::new(&s);
-// 				if !p.exists() {
-// 					break;
-// 				}
-// 				data_file.push(File::open(p)?);
-// 				i += 1;
-// 			}
-
-// 			(dir_file, data_file)
-// 		};
-
-
-// 		let header = {
-// 			let mut buf = [0u8; data::HEADER_SIZE];
-// 			dir_file.read_exact_at(&mut buf, 0)?;
-// 			crate::resource::read_from_bytes::<data::HeaderV1>(buf.as_slice())
-// 		};
-
-// 		let directory = {
-// 			let mut buf = Vec::<u8>::with_capacity(header.tree_size as usize);
-// 			buf.resize(header.tree_size as usize, 0);
-// 			dir_file.read_exact_at(&mut buf, data::HEADER_SIZE.try_into().unwrap())?;
-// 			super::helpers::read_directory_tree(buf.as_slice(), data::HEADER_SIZE.try_into().unwrap())
-// 		};
-
-// 		return Ok(VPKv1 {
-// 			dir_file,
-// 			data_file,
-// 			directory,
-// 		})
-// 	}
-// }
-
-// impl EntryDirectorySection for VPKv1 {
-// 	type EntryHandle = DirectoryEntry;
-
-// 	fn read_entry_data(&self, entry : &Self::EntryHandle) -> Result<Vec<u8>, crate::resource::error::Error> {
-// 		let mut full_data = Vec::<u8>::new();
-// 		full_data.resize(entry.entry_data.preload_bytes_size as usize + entry.entry_data.data_length as usize, 0);
-// 		let (preload_data, archive_data) = full_data.split_at_mut(entry.entry_data.preload_bytes_size as usize);
-		
-// 		/* Read preload data */
-// 		if entry.entry_data.preload_bytes_size != 0 {
-// 			(&self.dir_file).read_exact_at(preload_data, entry.preload_data_position.into())?;
-// 		}
-
-// 		if entry.entry_data.data_length == 0 {
-// 			return Ok(full_data);
-// 		}
-
-// 		if entry.entry_data.archive_index == super::data::DIRECTORYENTRY_DATA_IN_DIRECTORY_ARCHIVE_INDEX {
-// 			todo!()
-// 		} else {
-// 			(&self.data_file[entry.entry_data.archive_index as usize])
-// 				.read_exact_at(archive_data, entry.entry_data.data_offset.into())?;
-// 		}
-
-// 		return Ok(full_data);
-// 	}
-
-// 	fn get_entries<'a>(&'a self) -> &'a Vec<std::rc::Rc<Self::EntryHandle>> {
-// 		return &self.directory.entries;
-// 	}
-
-// 	fn get_entry_from_path(&self, path : String) -> Option<&Self::EntryHandle> {
-// 		match self.directory.map.get(&path) {
-// 			Some(x) => Some(x.as_ref()),
-// 			None => None,
-// 		}
-// 	}
-// }
\ No newline at end of file
+use super::*;
+
+/// Pure data types used to read file sections.
+mod data {
+	use serde::{Serialize, Deserialize};
+	use crate::resource::vpk::data::VPK_SIGNATURE;
+	use super::*;
+
+	#[derive(Clone, Debug, Serialize, Deserialize)]
+	pub struct HeaderV1 {
+		/// Should be `VPK_SIGNATURE`
+		pub signature : u32,
+		/// Should be 1
+		pub version : u32,
+
+		/// The size, in bytes, of the directory tree
+		pub tree_size : u32,
+	}
+
+	impl HeaderV1 {
+		pub const SIZE : usize = 12;
+
+		pub(super) const fn get_tree_start(&self) -> usize { Self::SIZE }
+		/// V1 has no section sizes for embedded data, it simply follows the tree.
+		pub(super) fn get_data_start(&self)       -> usize { self.get_tree_start() + self.tree_size as usize }
+
+		pub(super) fn is_valid(&self) -> Result<(), ErrorKind> {
+			if self.signature != VPK_SIGNATURE { return Err(ErrorKind::MalformedData("Signature at offset 0".to_string())); }
+			if self.version != 1 { return Err(ErrorKind::MalformedData("Major Version at offset 4".to_string())); }
+			Ok(())
+		}
+	}
+}
+
+use super::Reader;
+pub use data::HeaderV1;
+pub use super::directory::Handle      as EntryHandleV1;
+pub use super::directory::EntryReader as EntryReaderV1;
+
+/// VPK V1 file
+/// 
+/// Used in the following titles:
+/// - Alien Swarm
+/// - Dota 2
+/// - Left 4 Dead
+/// - Left 4 Dead 2
+/// - Portal 2
+/// - Source Filmmaker
+pub struct VPKv1 {
+	raw_header : data::HeaderV1,
+
+	directory : directory::Directory,
+	dir : Reader,
+	data : Vec<Reader>,
+}
+
+impl VPKv1 {
+	/// Opens a VPK from caller provided sources rather than files on disk.
+	/// 
+	/// # Arguments
+	/// * `dir` - The directory file, e.g. the contents of `pak01_dir.vpk`.
+	/// * `data` - Each data archive in index order, e.g. `pak01_000.vpk`, `pak01_001.vpk`...
+	/// 
+	/// Sources can be in memory buffers such as `Vec<u8>` or any `Read + Seek` wrapped in `ReadSeekAt`.
+	pub fn open_from_readers(dir : Reader, data : Vec<Reader>) -> Result<Self, ErrorKind> {
+		let header : data::HeaderV1 = {
+			let buf = helpers::read_dir_section(dir.as_ref(), 0, data::HeaderV1::SIZE.try_into().unwrap(), "Header")?;
+			bincode::deserialize(&buf)?
+		};
+
+		header.is_valid()?;
+
+		let directory = {
+			let start = header.get_tree_start().try_into().unwrap();
+			let buf = helpers::read_dir_section(dir.as_ref(), start, header.tree_size.into(), "Directory tree")?;
+			directory::read_directory_tree(
+				buf.as_slice(),
+				start,
+				header.get_data_start().try_into().unwrap()
+			)?
+		};
+
+		Ok(VPKv1 {
+			raw_header : header,
+			directory,
+			dir,
+			data,
+		})
+	}
+
+	/// Opens a VPK from exactly the given files, for sets which don't follow the `_dir.vpk`/`_NNN.vpk` naming convention.
+	/// 
+	/// # Arguments
+	/// * `dir_path` - The directory file.
+	/// * `data_paths` - Each data archive in index order.
+	/// 
+	/// # Errors
+	/// * `DoesNotExist` - When any of the files don't exist.
+	pub fn open_from_paths(dir_path : &Path, data_paths : &[std::path::PathBuf]) -> Result<Self, ErrorKind> {
+		let (dir_file, data_file) = helpers::open_files(dir_path, data_paths)?;
+		Self::open_from_readers(dir_file, data_file)
+	}
+
+	/// The header of the directory file.
+	pub fn header(&self) -> &data::HeaderV1 {
+		&self.raw_header
+	}
+
+	/// The number of data archives opened alongside the directory file.
+	pub fn archive_count(&self) -> usize {
+		self.data.len()
+	}
+}
+
+impl Open for VPKv1 {
+	fn open_from_path(path : &Path) -> Result<Self, ErrorKind> {
+		let (dir_file, data_file) = helpers::open_archive_files(path)?;
+		VPKv1::open_from_readers(dir_file, data_file)
+	}
+}
+
+impl Extract for VPKv1 {
+	type EntryReader = EntryReaderV1;
+
+	fn get_entry_from_path(&self, path : &str) -> Result<Self::EntryReader, ErrorKind> {
+		self.directory.get_entry_reader(path, &self.dir, &self.data)
+	}
+}
+
+impl List for VPKv1 {
+	fn get_handles(&self) -> Vec<&EntryHandle> {
+		self.directory.get_handles()
+	}
+
+	fn get_handle_from_path(&self, path : &str) -> Option<&EntryHandle> {
+		self.directory.get_handle_from_path(path)
+	}
+}
+
+impl ValidateEntries for VPKv1 {
+	fn validate_entry(&self, path : &str) -> Result<bool, ErrorKind> {
+		self.directory.validate_entry(path, &self.dir, &self.data)
+	}
+
+	fn validate_entries(&self) -> Result<Vec<&str>, ErrorKind> {
+		self.directory.validate_entries(&self.dir, &self.data)
+	}
+}
@@ -1,22 +1,29 @@
-use std::cell::RefCell;
 use std::{fs::File, path::Path};
-use std::io::prelude::*;
 use super::*;
 use crate::resource::vpk::data as common_data;
 
 mod data;
-mod directory;
 mod create;
+mod from_directory;
+mod manifest;
 mod open;
+mod rules;
+mod signature;
 
-pub use directory::Handle      as EntryHandleV2;
-pub use directory::EntryReader as EntryReaderV2;
+pub use super::{ReadSeek, ReadAt, ReadSeekAt, Reader};
+pub use super::directory::Handle      as EntryHandleV2;
+pub use super::directory::EntryReader as EntryReaderV2;
 pub use create::EntryPrototype as EntryPrototypeV2;
-
-pub trait ReadSeek : Read + Seek {}
-impl ReadSeek for File {}
-impl ReadSeek for std::io::Cursor<&[u8]> {}
-type Reader = Rc<RefCell<Box<dyn ReadSeek>>>;
+pub use create::CreateOptions  as CreateOptionsV2;
+pub use create::CreateSummary  as CreateSummaryV2;
+pub use create::RebuildSummary as RebuildSummaryV2;
+pub use create::VALVE_ARCHIVE_MD5_BLOCK_SIZE;
+pub use super::DirectoryFilter;
+pub use rules::{PackRule, PackRules};
+pub use signature::SigningKey  as SigningKeyV2;
+pub use signature::{public_key_from_pem, public_key_from_valve_key_file};
+pub use data::HeaderV2;
+pub use manifest::{Manifest, ManifestEntry, ManifestArchiveMD5, ManifestOtherMD5, ManifestSignature, MANIFEST_SCHEMA_VERSION};
 
 /// VPK V2 file
 /// 
@@ -39,54 +46,59 @@ pub struct VPKv2 {
 	data : Vec<Reader>,
 	archive_md5 : Vec<common_data::ArchiveMD5SectionEntry>,
 	other_md5 : common_data::OtherMD5Section,
+	signature : Option<common_data::SignatureSection>,
 }
 
 impl Extract for VPKv2 {
 	type EntryReader = EntryReaderV2;
 	
 	fn get_entry_from_path(&self, path : &str) -> Result<Self::EntryReader, ErrorKind> {
-		match self.directory.map.get(path) {
-			Some(x) => {
-				let handle = (**x).clone();
-				Ok(
-					Self::EntryReader::new(
-						handle,
-						self.dir.clone(),
-						if x.entry.is_in_directory_archive() {
-							None
-						} else {
-							Some(self.data[usize::from(x.entry.archive_index)].clone())
-						}
-					)
-				)
-			},
-			None => Err(ErrorKind::DoesNotExist("".to_string())),
-		}
+		self.directory.get_entry_reader(path, &self.dir, &self.data)
+	}
+}
+
+impl List for VPKv2 {
+	fn get_handles(&self) -> Vec<&EntryHandle> {
+		self.directory.get_handles()
+	}
+
+	fn get_handle_from_path(&self, path : &str) -> Option<&EntryHandle> {
+		self.directory.get_handle_from_path(path)
+	}
+}
+
+impl ValidateEntries for VPKv2 {
+	fn validate_entry(&self, path : &str) -> Result<bool, ErrorKind> {
+		self.directory.validate_entry(path, &self.dir, &self.data)
+	}
+
+	fn validate_entries(&self) -> Result<Vec<&str>, ErrorKind> {
+		self.directory.validate_entries(&self.dir, &self.data)
 	}
 }
 
 impl ValidateArchive for VPKv2 {
 	type Checksum = common_data::ArchiveMD5SectionEntry;
 	
+	/// Checks each checksum against its range of archive data, whether written per entry or per block.
+	/// 
+	/// Ranges which can't be read, e.g. in a missing or truncated archive, are reported as failed.
 	fn validate_archive(&self) -> Vec<&Self::Checksum> {
-		let mut failed = Vec::<&Self::Checksum>::new();
-		
-		for entry in &self.archive_md5 {
-			let mut buf = Vec::<u8>::new();
-			buf.resize(usize::try_from(entry.count).unwrap(), 0);
-			
-			let mut data = (&self.data[usize::try_from(entry.archive_index).unwrap()]).borrow_mut();
-
-			data.seek(std::io::SeekFrom::Start(entry.starting_offset.into())).expect("File error when reading archive for validation");
-			data.read_exact(&mut buf).expect("File error when reading archive for validation");
-			
-			let data_digest = md5::compute(buf.as_slice());
-			if data_digest.0 != entry.md5_checksum {
-				failed.push(entry);
+		fn digest(data : &dyn ReadAt, entry : &common_data::ArchiveMD5SectionEntry) -> std::io::Result<[u8; 16]> {
+			let mut section = helpers::SectionReader::new(data, entry.starting_offset.into()).take(entry.count.into());
+			let mut md5 = md5::Context::new();
+			if std::io::copy(&mut section, &mut md5)? != u64::from(entry.count) {
+				return Err(std::io::ErrorKind::UnexpectedEof.into());
 			}
+			Ok(md5.compute().0)
 		}
 
-		failed
+		self.archive_md5.iter().filter(|entry| {
+			match self.data.get(usize::try_from(entry.archive_index).unwrap()) {
+				Some(data) => digest(data.as_ref(), entry).map_or(true, |d| d != entry.md5_checksum),
+				None => true,
+			}
+		}).collect()
 	}
 }
 
@@ -94,8 +106,7 @@ impl ValidateOther for VPKv2 {
 	fn validate_other(&self) -> Result<(), ErrorKind> {
 		/* tree_checksum */ {
 			let mut buf = vec![0u8; self.raw_header.tree_size.try_into().unwrap()];
-			self.dir.borrow_mut().seek(std::io::SeekFrom::Start(self.raw_header.get_tree_start().try_into().unwrap()))?;
-			self.dir.borrow_mut().read_exact(&mut buf)?;
+			self.dir.read_exact_at(&mut buf, self.raw_header.get_tree_start().try_into().unwrap())?;
 			
 			let data_digest = md5::compute(buf.as_slice());
 			if data_digest.0 != self.other_md5.tree_checksum {
@@ -105,8 +116,7 @@ impl ValidateOther for VPKv2 {
 
 		/* archive_md5_section_checksum */ {
 			let mut buf = vec![0u8; self.raw_header.archive_md5_section_size.try_into().unwrap()];
-			self.dir.borrow_mut().seek(std::io::SeekFrom::Start(self.raw_header.get_archive_md5_start().try_into().unwrap()))?;
-			self.dir.borrow_mut().read_exact(&mut buf)?;
+			self.dir.read_exact_at(&mut buf, self.raw_header.get_archive_md5_start().try_into().unwrap())?;
 			
 			let data_digest = md5::compute(buf.as_slice());
 			if data_digest.0 != self.other_md5.archive_md5_section_checksum {
@@ -1,11 +1,93 @@
-use std::{convert::TryInto, convert::TryFrom, collections::HashMap, io::SeekFrom};
+use std::{convert::TryInto, convert::TryFrom, collections::BTreeMap, collections::HashMap, collections::HashSet, io::SeekFrom, path::PathBuf};
 use std::io::prelude::*;
 
-/// The size at which a new data file is created
+/// The default size at which a new data file is created
 const DATA_SPLIT_BYTE : u64 = 100 * 1000 * 1000; /* 100MB */
+/// The size of the blocks entry data is copied in, this bounds memory use regardless of entry size.
+const COPY_BLOCK_SIZE : usize = 64 * 1024;
+/// The size of the blocks Valve's tools checksum data archives in, see `CreateOptions::archive_md5_block_size`.
+pub const VALVE_ARCHIVE_MD5_BLOCK_SIZE : u32 = 1024 * 1024; /* 1MiB */
 
 use super::*;
 
+/// Details of a VPK written by `VPKv2::create_with_options`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CreateSummary {
+	/// Entries sharing the data of an identical entry written before them, see `CreateOptions::deduplicate`.
+	pub deduplicated_entries : usize,
+	/// The number of bytes not written due to deduplication.
+	pub bytes_saved : u64,
+}
+
+/// The data archives kept and written by `VPKv2::rebuild`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RebuildSummary {
+	/// Existing archives holding the data of unchanged entries, which are never rewritten.
+	pub kept_archives : Vec<u16>,
+	/// New archives after the existing ones, holding the changed and new entries.
+	pub written_archives : Vec<u16>,
+	/// Existing archives no entry refers to anymore, left in place as removing them would renumber the archives after them.
+	pub orphaned_archives : Vec<u16>,
+	/// Existing archives past the last one still in use, deleted when no new archives were written.
+	pub removed_archives : Vec<u16>,
+}
+
+/// Settings used when creating a VPK.
+#[derive(Clone, Debug)]
+pub struct CreateOptions {
+	/// The size at which a new data archive is started.
+	/// 
+	/// An entry is never split across archives, if it would take an archive past this size it starts a new one.
+	/// Entries larger than this are given an archive of their own.
+	/// Must not exceed `u32::MAX` as offsets into an archive are 32 bit.
+	pub chunk_size : u64,
+	/// Writes entry data in path order rather than the order entries are given in.
+	/// 
+	/// The output then only depends on the set of entries, making it byte for byte reproducible.
+	/// The directory tree is always sorted.
+	pub reproducible : bool,
+	/// Writes a standalone `<name>.vpk` instead of `<name>_dir.vpk`, such as a workshop addon.
+	/// 
+	/// Every entry's data is embedded in that one file regardless of `store_in_directory` and no data archives are created.
+	pub single_file : bool,
+	/// Signs the directory file with this key pair, as `vpk.exe -k` does.
+	pub signing_key : Option<signature::SigningKey>,
+	/// Overrides the preload size, storage location and group of each entry matching a rule.
+	pub pack_rules : Option<rules::PackRules>,
+	/// Writes an archive checksum for every block of this many bytes in each data archive rather than one per entry.
+	/// 
+	/// Valve's tools use `VALVE_ARCHIVE_MD5_BLOCK_SIZE`, which keeps the checksum section small for VPKs with many entries.
+	pub archive_md5_block_size : Option<u32>,
+	/// Stores identical data once, pointing every entry with that data at the same archive and offset.
+	/// 
+	/// Entries are compared by a SHA-256 hash of the data after their preload, which requires reading each entry's data twice.
+	/// Only entries in the same group and stored in the same kind of archive share data.
+	pub deduplicate : bool,
+}
+
+impl Default for CreateOptions {
+	fn default() -> Self {
+		Self {
+			chunk_size: DATA_SPLIT_BYTE,
+			reproducible: false,
+			single_file: false,
+			signing_key: None,
+			pack_rules: None,
+			archive_md5_block_size: None,
+			deduplicate: false,
+		}
+	}
+}
+
+impl CreateOptions {
+	fn is_valid(&self) -> Result<(), ErrorKind> {
+		if self.chunk_size == 0 { return Err(ErrorKind::MalformedData("Chunk size must not be 0".to_string())); }
+		if self.archive_md5_block_size == Some(0) { return Err(ErrorKind::MalformedData("Archive MD5 block size must not be 0".to_string())); }
+		if self.chunk_size > u32::MAX.into() { return Err(ErrorKind::TooLarge(format!("Chunk size {} is greater than the maximum archive offset", self.chunk_size))); }
+		Ok(())
+	}
+}
+
 /// An incomplete entry for the user to apply settings to.
 pub struct EntryPrototype {
 	///How much of this entry is stored in preload data
@@ -18,11 +100,18 @@ pub struct EntryPrototype {
 	path : String,
 
 	data : Box<dyn ReadSeek>,
-
-	raw : data::DirectoryEntryData,
+	/// Entries are written to data archives by group, see `PackRule::group`.
+	group : String,
+	/// Preload data kept from `write_entries` so the data doesn't need to be read again for the tree.
+	preload : Vec<u8>,
+	/// Set by `VPKv2::rebuild` when the archive data is already in an existing data archive, `raw` then locates it.
+	reused : bool,
+
+	raw : common_data::DirectoryEntryData,
 }
 
 impl EntryPrototype {
+	/// An empty `path` or `extension` is stored as Valve's `" "` placeholder.
 	pub fn new(
 		store_in_directory : bool,
 		preload_size : u16,
@@ -31,202 +120,826 @@ impl EntryPrototype {
 		extension : String,
 		data : Box<dyn ReadSeek>,
 	) -> Self {
+		let placeholder = |s : String| if s.is_empty() { directory::EMPTY_COMPONENT.to_string() } else { s };
 		EntryPrototype {
 			preload_size,
 			store_in_directory,
-			extension,
+			extension : placeholder(extension),
 			filename,
-			path,
+			path : placeholder(path),
 			data,
-			raw: data::DirectoryEntryData::default(),
+			group : String::new(),
+			preload : Vec::new(),
+			reused : false,
+			raw: common_data::DirectoryEntryData::default(),
+		}
+	}
+
+	/// Creates an entry from its full path, splitting it into the path, filename and extension stored in the tree.
+	/// 
+	/// e.g. `materials/example.vmt`, `addoninfo.txt` (no path) or `bin/README` (no extension).
+	pub fn from_path(store_in_directory : bool, preload_size : u16, full_path : &str, data : Box<dyn ReadSeek>) -> Self {
+		let (path, filename, extension) = directory::split_entry_path(full_path);
+		Self::new(store_in_directory, preload_size, path, filename, extension, data)
+	}
+
+	/// The path this entry will be found at once packed, e.g. `materials/example.vmt`.
+	fn full_path(&self) -> String {
+		directory::join_entry_path(&self.path, &self.filename, &self.extension)
+	}
+
+	/// Creates this entry's tree node, the entry must have been set up by `write_entries` first.
+	fn create_tree_node(&mut self) -> TreeNode {
+		TreeNode {
+			extension : self.extension.clone(),
+			path : self.path.clone(),
+			filename : self.filename.clone(),
+			raw : self.raw.clone(),
+			preload : std::mem::take(&mut self.preload),
+		}
+	}
+}
+
+/// An entry ready to be written to the directory tree.
+struct TreeNode {
+	extension : String,
+	path : String,
+	filename : String,
+	raw : common_data::DirectoryEntryData,
+	preload : Vec<u8>,
+}
+
+/// Checksums archive data in fixed size blocks, as Valve's tools do, rather than per entry.
+struct BlockHasher {
+	block_size : u64,
+	index : u16,
+	/// Offset of the next byte to be hashed.
+	position : u32,
+	/// Start and running hash of the block being filled.
+	current : Option<(u32, md5::Context)>,
+}
+
+impl BlockHasher {
+	fn new(block_size : u32) -> Self {
+		BlockHasher { block_size : block_size.into(), index : 0, position : 0, current : None }
+	}
+
+	/// Starts hashing a new archive at `position`, `finish` must have been called for the previous archive.
+	fn start(&mut self, index : u16, position : u32) {
+		self.index = index;
+		self.position = position;
+	}
+
+	fn update(&mut self, mut data : &[u8], archivemd5 : &mut Vec<common_data::ArchiveMD5SectionEntry>) {
+		while !data.is_empty() {
+			let (start, md5) = self.current.get_or_insert_with(|| (self.position, md5::Context::new()));
+			let filled = u64::from(self.position - *start);
+			let take = std::cmp::min(self.block_size - filled, data.len() as u64) as usize;
+			md5.consume(&data[..take]);
+			self.position += u32::try_from(take).unwrap();
+			data = &data[take..];
+
+			if u64::from(self.position - *start) == self.block_size {
+				self.finish(archivemd5);
+			}
+		}
+	}
+
+	/// Writes the checksum of the partially filled block, if any.
+	fn finish(&mut self, archivemd5 : &mut Vec<common_data::ArchiveMD5SectionEntry>) {
+		if let Some((start, md5)) = self.current.take() {
+			archivemd5.push(common_data::ArchiveMD5SectionEntry {
+				archive_index : self.index.into(),
+				starting_offset : start,
+				count : self.position - start,
+				md5_checksum : md5.compute().0,
+			});
+		}
+	}
+}
+
+/// Writes entry data into sequentially numbered data archives.
+struct ArchiveWriter<'a> {
+	directory_path : &'a Path,
+	filename : &'a str,
+	chunk_size : u64,
+	/// Index of the archive currently being written to.
+	index : u16,
+	/// The index given to the next new archive.
+	next_index : u16,
+	/// Creates archives under temporary names, the caller moves them into place once everything is written.
+	staged : bool,
+	/// Indices of the archives created by this writer, in the order they were created.
+	written : Vec<u16>,
+	/// The existing archive continued by `resume` and its length before anything was added.
+	resumed : Option<(u16, u64)>,
+	file : Option<File>,
+	/// The group of the entries in the current archive.
+	group : String,
+	/// Present when checksums are written per block rather than per entry.
+	blocks : Option<BlockHasher>,
+}
+
+impl<'a> ArchiveWriter<'a> {
+	fn new(directory_path : &'a Path, filename : &'a str, options : &CreateOptions) -> Self {
+		ArchiveWriter {
+			directory_path,
+			filename,
+			chunk_size : options.chunk_size,
+			index : 0,
+			next_index : 0,
+			staged : false,
+			written : Vec::new(),
+			resumed : None,
+			file : None,
+			group : String::new(),
+			blocks : options.archive_md5_block_size.map(BlockHasher::new),
+		}
+	}
+
+	/// Continues writing at the end of an existing archive holding entries of `group`.
+	/// 
+	/// With block checksums, a partial block at the end of the archive is removed from `archivemd5` and continued.
+	/// When `group` is `None` the archive's group isn't known, so it's left as is and writing starts with a new archive.
+	fn resume(
+		directory_path : &'a Path,
+		filename : &'a str,
+		options : &CreateOptions,
+		index : u16,
+		group : Option<String>,
+		archivemd5 : &mut Vec<common_data::ArchiveMD5SectionEntry>,
+	) -> Result<Self, ErrorKind> {
+		let Some(group) = group else {
+			let mut writer = ArchiveWriter::new(directory_path, filename, options);
+			writer.next_index = index + 1;
+			return Ok(writer);
+		};
+
+		let mut file = std::fs::OpenOptions::new().read(true).write(true).open(get_archive_path(directory_path, filename, index))?;
+		let end = u32::try_from(file.seek(SeekFrom::End(0))?)
+			.map_err(|_| ErrorKind::TooLarge(format!("Archive {} is greater than the maximum archive offset", index)))?;
+
+		let mut writer = ArchiveWriter::new(directory_path, filename, options);
+		writer.index = index;
+		writer.next_index = index + 1;
+		writer.group = group;
+		writer.resumed = Some((index, end.into()));
+		if let Some(blocks) = writer.blocks.as_mut() {
+			let partial = archivemd5.iter().position(|c| {
+				c.archive_index == u32::from(index) && c.starting_offset.checked_add(c.count) == Some(end) && u64::from(c.count) < blocks.block_size
+			});
+			match partial {
+				Some(i) => {
+					let partial = archivemd5.remove(i);
+					blocks.start(index, partial.starting_offset);
+					file.seek(SeekFrom::Start(partial.starting_offset.into()))?;
+					copy_blocks(&mut file, &mut std::io::sink(), partial.count.into(), |block| blocks.update(block, archivemd5))?;
+				},
+				None => blocks.start(index, end),
+			}
+		}
+		writer.file = Some(file);
+		Ok(writer)
+	}
+
+	/// Writes to temporary files, adding archives from `next_index`.
+	///
+	/// The temporary files are at `staged_path` for each index in `written`.
+	fn staged(directory_path : &'a Path, filename : &'a str, options : &CreateOptions, next_index : u16) -> Self {
+		let mut writer = ArchiveWriter::new(directory_path, filename, options);
+		writer.next_index = next_index;
+		writer.staged = true;
+		writer
+	}
+
+	/// Where the archive at `index` is written while staged.
+	fn staged_path(&self, index : u16) -> PathBuf {
+		get_archive_path(self.directory_path, self.filename, index).with_extension("vpk.tmp")
+	}
+
+	/// Undoes everything written after a failure, truncating the resumed archive and removing the archives this writer created.
+	fn roll_back(&mut self) {
+		self.file = None;
+		if let Some((index, length)) = self.resumed {
+			if let Ok(file) = std::fs::OpenOptions::new().write(true).open(get_archive_path(self.directory_path, self.filename, index)) {
+				let _ = file.set_len(length);
+			}
+		}
+		for index in &self.written {
+			let path = if self.staged { self.staged_path(*index) } else { get_archive_path(self.directory_path, self.filename, *index) };
+			let _ = std::fs::remove_file(path);
 		}
 	}
+
+	/// Copies the archive data of `e` to the current archive, creating it if needed.
+	///
+	/// # Arguments
+	/// * `e` - The entry being written, its data reader must be positioned after the preload data.
+	/// * `crc` - The entry's CRC to be updated with the archive data.
+	/// * `archivemd5` - Checksums of the written data are added here.
+	fn write_entry(&mut self, e : &mut EntryPrototype, crc : &mut crc32fast::Hasher, archivemd5 : &mut Vec<common_data::ArchiveMD5SectionEntry>) -> Result<(), ErrorKind> {
+		if let Some(file_data) = self.file.as_mut() {
+			let position = file_data.stream_position()?;
+			let too_large = position + u64::from(e.raw.data_length) > self.chunk_size; /* Entry would take the file over the size limit */
+			if position > 0 && (too_large || e.group != self.group) { /* Groups never share an archive */
+				file_data.flush()?;
+				self.file = None;
+			} else if position == 0 {
+				self.group = e.group.clone();
+			}
+		}
+
+		if self.file.is_none() {
+			if self.next_index >= common_data::DirectoryEntryData::DATA_IN_DIRECTORY_ARCHIVE_INDEX {
+				return Err(ErrorKind::TooLarge(format!("Data for {} requires more than {} archives", e.full_path(), self.next_index)));
+			}
+			self.index = self.next_index;
+			self.next_index += 1;
+			let path = if self.staged {
+				self.staged_path(self.index)
+			} else {
+				get_archive_path(self.directory_path, self.filename, self.index)
+			};
+			self.file = Some(File::create(path)?);
+			self.written.push(self.index);
+			self.group = e.group.clone();
+			if let Some(blocks) = self.blocks.as_mut() {
+				blocks.finish(archivemd5);
+				blocks.start(self.index, 0);
+			}
+		}
+		let file_data = self.file.as_mut().unwrap(); /* Okay because of the create above */
+
+		e.raw.archive_index = self.index;
+		e.raw.data_offset = file_data.stream_position()?.try_into()
+			.map_err(|_| ErrorKind::TooLarge(format!("Offset of {} is greater than the maximum archive offset", e.full_path())))?;
+
+		match self.blocks.as_mut() {
+			Some(blocks) => {
+				copy_blocks(&mut e.data, file_data, e.raw.data_length.into(), |block| {
+					blocks.update(block, archivemd5);
+					crc.update(block);
+				})?;
+			},
+			None => {
+				let mut md5 = md5::Context::new();
+				copy_blocks(&mut e.data, file_data, e.raw.data_length.into(), |block| {
+					md5.consume(block);
+					crc.update(block);
+				})?;
+				archivemd5.push(common_data::ArchiveMD5SectionEntry {
+					archive_index: e.raw.archive_index.into(),
+					starting_offset: e.raw.data_offset,
+					count: e.raw.data_length,
+					md5_checksum: md5.compute().0,
+				});
+			},
+		}
+
+		Ok(())
+	}
+
+	/// Writes the checksum of the last partial block, must be called once all entries are written.
+	fn finish(&mut self, archivemd5 : &mut Vec<common_data::ArchiveMD5SectionEntry>) {
+		if let Some(blocks) = self.blocks.as_mut() {
+			blocks.finish(archivemd5);
+		}
+		self.file = None;
+	}
+}
+
+/// Copies exactly `length` bytes from `reader` to `writer` in blocks of `COPY_BLOCK_SIZE`.
+///
+/// # Arguments
+/// * `inspect` - Called with each block as it is copied, used to calculate checksums.
+fn copy_blocks<R : Read + ?Sized, W : Write + ?Sized>(reader : &mut R, writer : &mut W, length : u64, mut inspect : impl FnMut(&[u8])) -> Result<(), ErrorKind> {
+	let mut buf = vec![0u8; std::cmp::min(length, COPY_BLOCK_SIZE as u64).try_into().unwrap()];
+	let mut remaining = length;
+	while remaining > 0 {
+		let block = &mut buf[..std::cmp::min(remaining, COPY_BLOCK_SIZE as u64).try_into().unwrap()];
+		reader.read_exact(block)?;
+		inspect(block);
+		writer.write_all(block)?;
+		remaining -= u64::try_from(block.len()).unwrap();
+	}
+	Ok(())
+}
+
+/// Gets the path of a VPK file, `suffix` being either `dir` or the archive index.
+fn get_vpk_path(directory_path : &Path, filename : &str, suffix : &str) -> PathBuf {
+	let mut path = directory_path.to_path_buf();
+	path.push("./".to_owned() + filename + "_" + suffix + ".vpk");
+	path
+}
+
+/// Gets the path of the directory file, which has no suffix when creating a single file VPK.
+fn get_dir_path(directory_path : &Path, filename : &str, options : &CreateOptions) -> PathBuf {
+	if options.single_file {
+		directory_path.join(filename.to_owned() + ".vpk")
+	} else {
+		get_vpk_path(directory_path, filename, "dir")
+	}
+}
+
+fn get_archive_path(directory_path : &Path, filename : &str, index : u16) -> PathBuf {
+	get_vpk_path(directory_path, filename, &format!("{:0>3}", index))
+}
+
+/// Identifies an entry's data by whether it's embedded, its group and the SHA-256 hash of the data after its preload.
+type ContentKey = (bool, String, [u8; 32]);
+
+/// Finds entries whose data is identical to an entry already written, see `CreateOptions::deduplicate`.
+#[derive(Default)]
+struct Deduplicator {
+	/// Archive index, offset and CRC of the data written for each key.
+	written : HashMap<ContentKey, (u16, u32, crc32fast::Hasher)>,
+}
+
+impl Deduplicator {
+	/// Hashes the next `e.raw.data_length` bytes of `e`, returning the reader to where it started.
+	/// 
+	/// The CRC of just those bytes is also returned to be combined with the preload CRC of duplicates.
+	fn hash(e : &mut EntryPrototype, embedded : bool) -> Result<(ContentKey, crc32fast::Hasher), ErrorKind> {
+		use sha2::Digest;
+
+		let start = e.data.stream_position()?;
+		let mut sha = sha2::Sha256::new();
+		let mut crc = crc32fast::Hasher::new();
+		copy_blocks(&mut e.data, &mut std::io::sink(), e.raw.data_length.into(), |block| {
+			sha.update(block);
+			crc.update(block);
+		})?;
+		e.data.seek(SeekFrom::Start(start))?;
+		Ok(((embedded, e.group.clone(), sha.finalize().into()), crc))
+	}
+}
+
+/// Finds the group of the entries in an existing archive by applying the pack rules to them, as groups aren't stored in the VPK.
+/// 
+/// # Returns
+/// `None` when its entries belong to different groups, e.g. when the rules have changed since they were written.
+fn archive_group(existing : &VPKv2, index : u16, options : &CreateOptions) -> Option<String> {
+	let mut groups = existing.directory.entries.iter()
+		.filter(|h| !h.entry.is_in_directory_archive() && !h.entry.is_preload_only() && h.entry.archive_index == index)
+		.map(|h| options.pack_rules.as_ref().and_then(|rules| rules.settings_for(&h.path).group).unwrap_or_default());
+	let group = groups.next().unwrap_or_default();
+	groups.all(|g| g == group).then_some(group)
+}
+
+/// Applies the pack rules to every entry and sets up as much of its `raw` data as possible before it's written.
+fn prepare_entries(entries : &mut [EntryPrototype], options : &CreateOptions) -> Result<(), ErrorKind> {
+	for e in entries.iter_mut() {
+		let data_len = e.data.seek(SeekFrom::End(0))?;
+		if let Some(rules) = &options.pack_rules {
+			let settings = rules.settings_for(&e.full_path());
+			if let Some(preload_size) = settings.preload_size {
+				e.preload_size = u16::try_from(data_len).map_or(preload_size, |len| preload_size.min(len));
+			}
+			if let Some(store_in_directory) = settings.store_in_directory { e.store_in_directory = store_in_directory; }
+			if let Some(group) = settings.group { e.group = group; }
+		}
+		let archive_len = data_len.checked_sub(e.preload_size.into())
+			.ok_or_else(|| ErrorKind::MalformedData(format!("Preload size of {} is greater than its data", e.full_path())))?;
+		e.raw.preload_bytes_size = e.preload_size;
+		e.raw.data_length = u32::try_from(archive_len)
+			.map_err(|_| ErrorKind::TooLarge(format!("Data length of {} is greater than the maximum entry size", e.full_path())))?;
+		e.raw.terminator = common_data::DirectoryEntryData::TERMINATOR;
+	}
+	Ok(())
+}
+
+/// Writes the data of every entry to the data archives or the embedded archive.
+///
+/// Also finishes each entry's `raw` data to be written to the directory tree, the entries must have been set up by `prepare_entries`.
+///
+/// # Arguments
+/// * `embeded_data` - Temporary storage for the embedded archive, entries are appended at the end.
+fn write_entries(
+	entries : &mut [EntryPrototype],
+	archives : &mut ArchiveWriter,
+	embeded_data : &mut File,
+	archivemd5 : &mut Vec<common_data::ArchiveMD5SectionEntry>,
+	options : &CreateOptions,
+) -> Result<CreateSummary, ErrorKind> {
+	let mut summary = CreateSummary::default();
+	let mut deduplicator = Deduplicator::default();
+
+	let mut order : Vec<usize> = (0..entries.len()).collect();
+	if options.reproducible {
+		order.sort_by_cached_key(|i| (entries[*i].group.clone(), entries[*i].full_path()));
+	} else {
+		order.sort_by(|a, b| entries[*a].group.cmp(&entries[*b].group)); /* Stable so each group keeps the given order */
+	}
+
+	/* Loop to write the actual entry data to the appropriate place */
+	for i in order {
+		let e = &mut entries[i];
+		let mut crc = crc32fast::Hasher::new();
+
+		/* Preload data is kept to be written with the directory tree */ {
+			e.data.seek(SeekFrom::Start(0))?;
+			let mut buf = vec![0u8; e.preload_size.into()];
+			e.data.read_exact(&mut buf)?;
+			crc.update(&buf);
+			e.preload = buf;
+		}
+
+		if e.reused { /* The data and CRC are already in place */
+			continue;
+		}
+
+		let embedded = e.store_in_directory || options.single_file;
+		let hash = if options.deduplicate && !e.raw.is_preload_only() { Some(Deduplicator::hash(e, embedded)?) } else { None };
+		if let Some((index, offset, archive_crc)) = hash.as_ref().and_then(|(key, _)| deduplicator.written.get(key)) {
+			e.raw.archive_index = *index;
+			e.raw.data_offset = *offset;
+			crc.combine(archive_crc);
+			e.raw.crc = crc.finalize();
+			summary.deduplicated_entries += 1;
+			summary.bytes_saved += u64::from(e.raw.data_length);
+			continue;
+		}
+
+		if e.raw.is_preload_only() {
+			if options.single_file { /* Nothing can refer to a data archive */
+				e.raw.archive_index = common_data::DirectoryEntryData::DATA_IN_DIRECTORY_ARCHIVE_INDEX;
+			}
+		} else if embedded {
+			e.raw.archive_index = common_data::DirectoryEntryData::DATA_IN_DIRECTORY_ARCHIVE_INDEX;
+			let offset = embeded_data.seek(SeekFrom::End(0))?;
+			if offset + u64::from(e.raw.data_length) > u32::MAX.into() {
+				return Err(ErrorKind::TooLarge(format!("Embedded data of {} is past the maximum embedded data size", e.full_path())));
+			}
+			e.raw.data_offset = offset.try_into().unwrap(); /* Okay because of the check above */
+			copy_blocks(&mut e.data, embeded_data, e.raw.data_length.into(), |block| crc.update(block))?;
+		} else { /* Write to data file */
+			archives.write_entry(e, &mut crc, archivemd5)?;
+		}
+
+		if let Some((key, archive_crc)) = hash {
+			deduplicator.written.insert(key, (e.raw.archive_index, e.raw.data_offset, archive_crc));
+		}
+		e.raw.crc = crc.finalize();
+	}
+	archives.finish(archivemd5);
+
+	Ok(summary)
+}
+
+/// Builds the directory tree from its entries.
+fn build_directory_tree(nodes : Vec<TreeNode>) -> Result<Vec<u8>, ErrorKind> {
+	/* Sorted maps keep the tree in the same order regardless of the order of `nodes` */
+	let mut maps = BTreeMap::<String /* Extension */, BTreeMap<String /* Path */, BTreeMap<String /* Filename */, TreeNode>>>::new();
+
+	/* Populate all maps with entries */
+	for e in nodes {
+		let filenames = maps
+			.entry(e.extension.clone()).or_default() /* Get paths */
+			.entry(e.path.clone()).or_default(); /* Get filenames */
+
+		if e.filename.is_empty() || e.path.is_empty() || e.extension.is_empty() {
+			return Err(ErrorKind::MalformedData(format!("File at {}/{}.{} has an empty path component", e.path, e.filename, e.extension)))
+		} else if filenames.contains_key(&e.filename) {
+			return Err(ErrorKind::AlreadyExists(format!("File at {}/{}.{} already exists", e.path, e.filename, e.extension)))
+		} else {
+			filenames.insert(e.filename.clone(), e);
+		}
+	}
+
+	let mut data = Vec::<u8>::new();
+
+	fn write_null_terminated_string(buf : &mut Vec<u8>, s : &String) -> Result<(), ErrorKind> {
+		if !s.is_ascii() { return Err(ErrorKind::MalformedData(format!("String \"{}\" is not ASCII", s)))}
+		buf.write_all(s.as_bytes())?;
+		buf.write_all(&[0])?; /* Null terminator */
+		Ok(())
+	}
+
+	/* Iterate maps to write directory tree */
+	for (ext, paths_map) in maps {
+		write_null_terminated_string(&mut data, &ext)?;
+		for (path, filenames_map) in paths_map {
+			write_null_terminated_string(&mut data, &path)?;
+			for (filename, e) in filenames_map {
+				write_null_terminated_string(&mut data, &filename)?;
+
+				/* Write entry */ {
+					let e_bytes = bincode::serialize(&e.raw).expect("Entry can't be serialized");
+					data.write_all(&e_bytes)?;
+				}
+
+				data.write_all(&e.preload)?;
+			}
+			data.write_all(&[0])?;
+		}
+		data.write_all(&[0])?;
+	}
+	data.write_all(&[0])?;
+
+	Ok(data)
+}
+
+/// Writes a complete dir file.
+///
+/// # Arguments
+/// * `embeded_data` - The complete embedded archive, copied in full from the start.
+/// * `signing_key` - When given the file is signed and the signature section appended.
+fn write_directory_file(
+	file_dir : &mut File,
+	dir_data : &[u8],
+	embeded_data : &mut File,
+	archivemd5 : &[common_data::ArchiveMD5SectionEntry],
+	signing_key : Option<&signature::SigningKey>,
+) -> Result<(), ErrorKind> {
+	let embeded_data_len = embeded_data.seek(SeekFrom::End(0))?;
+	let mut file_dir = signature::SigningWriter::new(file_dir, signing_key);
+	/* header */ {
+		let head = data::HeaderV2 {
+			tree_size : u32::try_from(dir_data.len()).map_err(|_| ErrorKind::TooLarge("Tree size is greater than a u32".to_string()))?,
+			file_data_section_size : u32::try_from(embeded_data_len).map_err(|_| ErrorKind::TooLarge("Embedded data size is greater than a u32".to_string()))?,
+			archive_md5_section_size : (common_data::ArchiveMD5SectionEntry::SIZE * archivemd5.len()).try_into()
+				.map_err(|_| ErrorKind::TooLarge("Archive MD5 section size is greater than a u32".to_string()))?,
+			other_md5_section_size : 48,
+			signature_section_size : signing_key.map_or(0, |k| k.section_size()).try_into().unwrap(),
+			..Default::default()
+		};
+		let bytes = bincode::serialize(&head)?;
+		file_dir.write_all(&bytes)?;
+	}
+
+	file_dir.write_all(dir_data)?;
+	embeded_data.seek(SeekFrom::Start(0))?;
+	copy_blocks(embeded_data, &mut file_dir, embeded_data_len, |_| {})?;
+
+	let archive_md5_checksum = { /* ArchiveMD5 */
+		let mut buf = Vec::<u8>::new();
+		for e in archivemd5 {
+			let mut bytes = bincode::serialize(&e)?;
+			buf.append(&mut bytes);
+		}
+		file_dir.write_all(&buf)?;
+		md5::compute(buf)
+	};
+
+	/* OtherMD5 */ {
+		let other = common_data::OtherMD5Section {
+			tree_checksum : md5::compute(dir_data).0,
+			archive_md5_section_checksum : *archive_md5_checksum,
+			..Default::default()
+		};
+
+		let bytes = bincode::serialize(&other)?;
+		file_dir.write_all(&bytes)?;
+	}
+
+	file_dir.finish()
 }
 
 impl VPKv2 {
 	/// Creates new VPK dir and data files.
-	/// 
+	///
 	/// # Arguments
 	/// * `directory_path` - The directory to create the files in.
 	/// * `filename` - Base name for the VPKs, e.g. `hl2_misc` -> `hl2_misc_dir.vpk`
 	/// * `entries` - A vector containing all of the entries to be packed.
 	pub fn create(directory_path : &Path, filename : &str, entries : &mut [EntryPrototype]) -> Result<(), ErrorKind> {
-		let mut file_dir = {
-			let mut path = directory_path.to_path_buf();
-			path.push("./".to_owned() + filename + "_dir.vpk");
-			File::create(path)?
-		};
-		
-		/* Set up as much of the entry as possible */
-		for e in entries.iter_mut() {
-			let data_len = e.data.seek(SeekFrom::End(0))?;
-			e.raw.preload_bytes_size = e.preload_size;
-			e.raw.data_length = u32::try_from(data_len).expect("Data length is greater than a u32") - u32::from(e.preload_size);
-			e.raw.terminator = data::DirectoryEntryData::TERMINATOR;
-		}
-		
-		/* TODO: Revise sizing concerns
-		 * u32 adresses allows up to 4.2GB
-		 * The data file division occurs every `DATA_SPLIT_BYTE` but what happens if a single 4.2GB file is inserted?
-		 */
-
-		let mut current_file_index : i32 = -1;
-		let mut file_data_option : Option<File> = None;
-		let mut embeded_data = Vec::<u8>::new();
+		Self::create_with_options(directory_path, filename, entries, &CreateOptions::default())?;
+		Ok(())
+	}
+
+	/// Creates new VPK dir and data files using the given settings.
+	///
+	/// # Arguments
+	/// * `directory_path` - The directory to create the files in.
+	/// * `filename` - Base name for the VPKs, e.g. `hl2_misc` -> `hl2_misc_dir.vpk`, or `addon` -> `addon.vpk` with `single_file`
+	/// * `entries` - A vector containing all of the entries to be packed.
+	/// * `options` - Settings controlling the layout of the VPK.
+	///
+	/// # Errors
+	/// * `TooLarge` - When an entry or the VPK can't be represented in the format.
+	pub fn create_with_options(directory_path : &Path, filename : &str, entries : &mut [EntryPrototype], options : &CreateOptions) -> Result<CreateSummary, ErrorKind> {
+		options.is_valid()?;
+		let mut file_dir = File::create(get_dir_path(directory_path, filename, options))?;
+
+		let mut archives = ArchiveWriter::new(directory_path, filename, options);
+		let mut embeded_data = tempfile::tempfile()?;
 		let mut archivemd5 = Vec::<common_data::ArchiveMD5SectionEntry>::new();
-		
-		/* Loop to write the actual entry data to the appropriate place */
+		prepare_entries(entries, options)?;
+		let summary = write_entries(entries, &mut archives, &mut embeded_data, &mut archivemd5, options)?;
+
+		let mut nodes = Vec::<TreeNode>::with_capacity(entries.len());
 		for e in entries.iter_mut() {
-			if file_data_option.is_none() {
-				current_file_index += 1;
-				let mut path = directory_path.to_path_buf();
-				path.push("./".to_string() + filename + "_" + &format!("{:0>3}", current_file_index).to_string() + ".vpk");
-				file_data_option = Some(File::create(path)?)
-			}
-			let mut file_data = file_data_option.as_ref().unwrap(); /* Okay because of the create above */
-
-			if e.raw.is_preload_only() {
-				continue; /* Preload data will be written later when the directory is written */
-			}
-
-			e.data.seek(SeekFrom::Start(e.preload_size.into())).expect("Entry data reader can't be seeked");
-			if e.store_in_directory {
-				e.raw.archive_index = data::DirectoryEntryData::DATA_IN_DIRECTORY_ARCHIVE_INDEX;
-				e.raw.data_offset = embeded_data.len().try_into().expect("Embeded data is larger than maximum address");
-
-				let mut buf = vec![0u8; e.raw.data_length.try_into().unwrap()];/*  Vec::<u8>::with_capacity(e.raw.data_length.try_into().unwrap()); */
-				e.data.read_exact(&mut buf)?;
-
-				embeded_data.append(&mut buf);
-			} else { /* Write to data file */
-				e.raw.archive_index = current_file_index.try_into().expect("File index is too large");
-				e.raw.data_offset = file_data.seek(SeekFrom::Current(0))?.try_into().expect("Data in file is larger than maximum address");
-				
-				let mut buf = vec![0u8; e.raw.data_length.try_into().unwrap()];
-				e.data.read_exact(&mut buf)?;
-
-				/* Calculate MD5 Entry */ {
-					archivemd5.push(
-						common_data::ArchiveMD5SectionEntry {
-							archive_index: current_file_index.try_into().unwrap(),
-							starting_offset: e.raw.data_offset,
-							count: e.raw.data_length,
-							md5_checksum: md5::compute(&buf).0,
-						}
-					)
-				}
+			nodes.push(e.create_tree_node());
+		}
+		let dir_data = build_directory_tree(nodes)?;
 
-				file_data.write_all(&buf)?;
+		write_directory_file(&mut file_dir, &dir_data, &mut embeded_data, &archivemd5, options.signing_key.as_ref())?;
+		Ok(summary)
+	}
+
+	/// Rewrites the `_dir` VPK appending new entries without rewriting the existing data files.
+	///
+	/// New archive data is added to the end of the last data file, or a new one if the last is full or holds a different group, see `PackRule::group`.
+	/// If anything fails the last data file is truncated back to its original length and any new data files are removed.
+	///
+	/// # Arguments
+	/// * `directory_path` - The directory containing the VPK.
+	/// * `filename` - Base name of the VPK, e.g. `hl2_misc` -> `hl2_misc_dir.vpk`
+	/// * `entries` - The entries to be added.
+	///
+	/// # Errors
+	/// * `AlreadyExists` - When an entry is already present in the VPK, nothing is written in this case.
+	pub fn append_entries(directory_path : &Path, filename : &str, entries : &mut [EntryPrototype]) -> Result<(), ErrorKind> {
+		Self::append_entries_with_options(directory_path, filename, entries, &CreateOptions::default())
+	}
 
-				if e.data.seek(SeekFrom::Current(0))? > DATA_SPLIT_BYTE { /* File is over the size limit */
-					file_data.flush()?;
-					file_data_option = None;
+	/// Appends new entries using the given settings, see `append_entries`.
+	/// 
+	/// Any existing signature is discarded, the directory is only signed again if `options` has a signing key.
+	pub fn append_entries_with_options(directory_path : &Path, filename : &str, entries : &mut [EntryPrototype], options : &CreateOptions) -> Result<(), ErrorKind> {
+		options.is_valid()?;
+		let dir_path = get_dir_path(directory_path, filename, options);
+		let existing = VPKv2::open_from_path(&dir_path)?;
+
+		/* Check for conflicts before anything is written */ {
+			let mut paths = HashSet::<String>::new();
+			for e in entries.iter() {
+				let path = e.full_path();
+				if existing.directory.map.contains_key(&path) || !paths.insert(path.clone()) {
+					return Err(ErrorKind::AlreadyExists(format!("File at {} already exists", path)));
 				}
 			}
 		}
-	
-		let dir_data = { /* Create entry directory */
-			let mut maps = HashMap::<String /* Extension */, HashMap<String /* Path */, HashMap<String /* Filename */, &mut EntryPrototype>>>::new();
 
-			/* Populate all hash maps with entries */
+		/* Collect the existing tree */
+		let mut nodes = Vec::<TreeNode>::with_capacity(existing.directory.entries.len() + entries.len());
+		for handle in &existing.directory.entries {
+			let mut preload = vec![0u8; handle.entry.preload_bytes_size.into()];
+			existing.dir.read_exact_at(&mut preload, handle.preload_data_position)?;
+
+			let (path, filename, extension) = handle.components();
+			nodes.push(TreeNode {
+				extension : extension.to_string(),
+				path : path.to_string(),
+				filename : filename.to_string(),
+				raw : handle.entry.clone(),
+				preload,
+			});
+		}
+
+		let mut embeded_data = tempfile::tempfile()?;
+		/* Existing embedded data is kept as is so its offsets remain valid */ {
+			let mut section = helpers::SectionReader::new(existing.dir.as_ref(), existing.raw_header.get_data_start().try_into().unwrap());
+			copy_blocks(&mut section, &mut embeded_data, existing.raw_header.file_data_section_size.into(), |_| {})?;
+		}
+
+		let mut archivemd5 = existing.archive_md5.clone();
+
+		let mut archives = match existing.data.len() {
+			0 => ArchiveWriter::new(directory_path, filename, options),
+			n => {
+				let index : u16 = (n - 1).try_into().map_err(|_| ErrorKind::TooLarge("Too many archives".to_string()))?;
+				let group = archive_group(&existing, index, options);
+				ArchiveWriter::resume(directory_path, filename, options, index, group, &mut archivemd5)?
+			},
+		};
+		drop(existing);
+
+		/* Write to a temporary file so the original is intact if anything fails */
+		let tmp_path = dir_path.with_extension("vpk.tmp");
+		let appended = (|| -> Result<(), ErrorKind> {
+			prepare_entries(entries, options)?;
+			write_entries(entries, &mut archives, &mut embeded_data, &mut archivemd5, options)?;
+
 			for e in entries.iter_mut() {
-				let filenames = maps
-					.entry(e.extension.clone()).or_default() /* Get paths */
-					.entry(e.path.clone()).or_default(); /* Get filenames */
-
-				if filenames.contains_key(&e.filename) {
-					return Err(ErrorKind::AlreadyExists(format!("File at {}/{}.{} already exists", e.path, e.filename, e.extension)))
-				} else {
-					filenames.insert(e.filename.clone(), e);
-				}
+				nodes.push(e.create_tree_node());
 			}
+			let dir_data = build_directory_tree(nodes)?;
+
+			let mut file_dir = File::create(&tmp_path)?;
+			write_directory_file(&mut file_dir, &dir_data, &mut embeded_data, &archivemd5, options.signing_key.as_ref())?;
+			drop(file_dir);
+			std::fs::rename(&tmp_path, &dir_path)?;
+			Ok(())
+		})();
+		if appended.is_err() {
+			archives.roll_back();
+			let _ = std::fs::remove_file(&tmp_path);
+		}
+		appended
+	}
 
-			let mut data = Vec::<u8>::new();
-
-			fn write_null_terminated_string(buf : &mut Vec<u8>, s : &String) -> Result<(), ErrorKind> {
-				if !s.is_ascii() { return Err(ErrorKind::MalformedData(format!("String \"{}\" is not ASCII", s)))}
-				buf.write_all(s.as_bytes())?; /* FIXME: Might work? might need a cusor */
-				buf.write_all(&[0])?; /* Null terminator */
-				Ok(())
-			}
-
-			/* Iterate hash maps to write directory tree */
-			for (ext, paths_map) in maps {
-				write_null_terminated_string(&mut data, &ext)?;
-				for (path, filenames_map) in paths_map {
-					write_null_terminated_string(&mut data, &path)?;
-					for (filename, e) in filenames_map {
-						write_null_terminated_string(&mut data, &filename)?;
-						
-						/* Write entry */ {
-							let e_bytes = bincode::serialize(&e.raw).expect("Entry can't be serialized");
-							data.write_all(&e_bytes)?;
-						}
-
-						/* Write preload data */ {
-							e.data.seek(SeekFrom::Start(0))?;
-							let mut buf = vec![0u8; e.preload_size.into()];
-							e.data.read_exact(&mut buf)?;
-							data.write_all(&buf)?;
-						}
-					}
-					data.write_all(&[0])?;
+	/// Rebuilds an existing VPK from a new set of entries, writing only changed and new data to new data archives.
+	///
+	/// An entry is unchanged when the VPK already holds identical data for its path with the same preload size, stored in a data archive.
+	/// Unchanged entries keep referring to their existing data, with their CRC computed while comparing in case the VPK was written without.
+	/// Other entries are written to new archives after the last existing one.
+	/// Existing archives are never rewritten so their numbers and contents stay the same, and a patch only needs the new archives and
+	/// the directory file. The directory file is rewritten in full, including any data embedded in it.
+	///
+	/// The old data of changed and removed entries is left in place as unused space, `create` the VPK again to reclaim it.
+	/// Trailing archives which are no longer used are deleted, but only when no new archives are written after them.
+	/// Other archives no entry refers to anymore are reported as orphaned.
+	///
+	/// Nothing is replaced until everything has been written, the new files are moved into place at the end.
+	///
+	/// # Arguments
+	/// * `directory_path` - The directory containing the VPK.
+	/// * `filename` - Base name of the VPK, e.g. `hl2_misc` -> `hl2_misc_dir.vpk`
+	/// * `entries` - Every entry of the rebuilt VPK, existing entries missing from this are removed.
+	/// * `options` - Settings used for the written archives and the directory file.
+	///
+	/// # Errors
+	/// * `AlreadyExists` - When two entries have the same path, nothing is written in this case.
+	pub fn rebuild(directory_path : &Path, filename : &str, entries : &mut [EntryPrototype], options : &CreateOptions) -> Result<RebuildSummary, ErrorKind> {
+		options.is_valid()?;
+		let dir_path = get_dir_path(directory_path, filename, options);
+		let existing = VPKv2::open_from_path(&dir_path)?;
+
+		/* Check for conflicts before anything is written */ {
+			let mut paths = HashSet::<String>::new();
+			for e in entries.iter() {
+				let path = e.full_path();
+				if !paths.insert(path.clone()) {
+					return Err(ErrorKind::AlreadyExists(format!("File at {} already exists", path)));
 				}
-				data.write_all(&[0])?;
 			}
-			data.write_all(&[0])?;
+		}
 
-			data
-		};
+		prepare_entries(entries, options)?;
 
-		/* Write dir file */ {
-			/* header */ {
-				let head = data::HeaderV2 {
-					tree_size : u32::try_from(dir_data.len()).expect("tree size is greater than a u32"),
-					file_data_section_size : u32::try_from(embeded_data.len()).expect("file data size is greater than a u32"),
-					archive_md5_section_size : (common_data::ArchiveMD5SectionEntry::SIZE * archivemd5.len()).try_into().unwrap(),
-					other_md5_section_size : 48,
-					..Default::default()
-				};
-				let bytes = bincode::serialize(&head)?;
-				file_dir.write_all(&bytes)?;
-			}
-
-			file_dir.write_all(&dir_data)?;
-			file_dir.write_all(&embeded_data)?;
-
-			let archive_md5_checksum = { /* ArchiveMD5 */
-				let mut buf = Vec::<u8>::new();
-				for e in archivemd5 {
-					let mut bytes = bincode::serialize(&e)?;
-					buf.append(&mut bytes);
-				}
-				file_dir.write_all(&buf)?;
-				md5::compute(buf)
-			};
+		/* Entries whose archive data is already in the VPK keep referring to it */
+		for e in entries.iter_mut() {
+			let path = e.full_path();
+			let Some(handle) = existing.directory.get_handle_from_path(&path) else { continue };
+			if e.store_in_directory || options.single_file || e.raw.is_preload_only() || handle.is_embedded()
+				|| handle.entry.preload_bytes_size != e.raw.preload_bytes_size || handle.entry.data_length != e.raw.data_length {
+				continue;
+			}
+			let Ok(mut reader) = existing.get_entry_from_path(&path) else { continue }; /* Missing data is written again */
+			e.data.seek(SeekFrom::Start(0))?;
+			let mut crc = crc32fast::Hasher::new();
+			if helpers::same_data(&mut e.data, &mut reader, handle.entry.total_data_size().into(), |block| crc.update(block))? {
+				e.raw = handle.entry.clone();
+				e.raw.crc = crc.finalize();
+				e.reused = true;
+			}
+		}
+
+		let archive_count = u16::try_from(existing.data.len()).map_err(|_| ErrorKind::TooLarge("Too many archives".to_string()))?;
+		let mut archivemd5 = existing.archive_md5.clone();
+		let mut archives = ArchiveWriter::staged(directory_path, filename, options, archive_count);
+		let mut embeded_data = tempfile::tempfile()?;
+		let tmp_path = dir_path.with_extension("vpk.tmp");
 
-			/* OtherMD5 */ {
-				let other = common_data::OtherMD5Section {
-					tree_checksum : md5::compute(&dir_data).0,
-					archive_md5_section_checksum : *archive_md5_checksum,
-					..Default::default()
-				};
+		let staged = (|| -> Result<u16, ErrorKind> {
+			write_entries(entries, &mut archives, &mut embeded_data, &mut archivemd5, options)?;
 
-				let bytes = bincode::serialize(&other)?;
-				file_dir.write_all(&bytes)?;
+			let mut nodes = Vec::<TreeNode>::with_capacity(entries.len());
+			for e in entries.iter_mut() {
+				nodes.push(e.create_tree_node());
 			}
+			let dir_data = build_directory_tree(nodes)?;
 
-			file_dir.flush()?;
-		}
+			/* Archives past the last one still in use are removed when nothing new follows them */
+			let used = if archives.written.is_empty() {
+				entries.iter().filter(|e| e.reused).map(|e| e.raw.archive_index + 1).max().unwrap_or(0)
+			} else {
+				archive_count
+			};
+			archivemd5.retain(|c| c.archive_index < used.into() || c.archive_index >= archive_count.into());
+			archivemd5.sort_by_key(|c| (c.archive_index, c.starting_offset));
+
+			let mut file_dir = File::create(&tmp_path)?;
+			write_directory_file(&mut file_dir, &dir_data, &mut embeded_data, &archivemd5, options.signing_key.as_ref())?;
+			Ok(used)
+		})();
+		let used = match staged {
+			Ok(used) => used,
+			Err(e) => {
+				archives.roll_back();
+				let _ = std::fs::remove_file(&tmp_path);
+				return Err(e);
+			},
+		};
+		drop(existing);
+
+		let referenced : HashSet<u16> = entries.iter().filter(|e| e.reused).map(|e| e.raw.archive_index).collect();
+		let summary = RebuildSummary {
+			kept_archives : (0..used).filter(|i| referenced.contains(i)).collect(),
+			written_archives : archives.written.clone(),
+			orphaned_archives : (0..used).filter(|i| !referenced.contains(i)).collect(),
+			removed_archives : (used..archive_count).collect(),
+		};
 
-		Ok(())
-	}
+		for index in &summary.written_archives {
+			std::fs::rename(archives.staged_path(*index), get_archive_path(directory_path, filename, *index))?;
+		}
+		for index in &summary.removed_archives {
+			std::fs::remove_file(get_archive_path(directory_path, filename, *index))?;
+		}
+		std::fs::rename(tmp_path, dir_path)?;
 
-	/// Rewrites the `_dir` VPK appending new entries without rewriting the data files.
-	pub fn append_entries(&self) {
-		todo!()
+		Ok(summary)
 	}
 }
-
-/* TODO: Tests */
-/* Single Large File (>4.2GB) */
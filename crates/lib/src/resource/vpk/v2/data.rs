@@ -4,7 +4,7 @@ use serde::{Serialize, Deserialize};
 use crate::resource::vpk::data::VPK_SIGNATURE;
 use super::*;
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct HeaderV2 {
 	/// Should be `VPK_SIGNATURE`
 	pub signature : u32,
@@ -33,10 +33,9 @@ impl HeaderV2 {
 	pub(super) fn get_signature_start(&self)   -> usize { self.get_other_md5_start()   + self.other_md5_section_size as usize }
 
 	pub(super) fn is_valid(&self) -> Result<(), ErrorKind> {
-		if self.signature != VPK_SIGNATURE { return Err(ErrorKind::MalformedData("Signature".to_string())); }
-		if self.version != 2 { return Err(ErrorKind::MalformedData("Major Version".to_string())); }
-		if self.other_md5_section_size != 48 { return Err(ErrorKind::MalformedData("OtherMD5".to_string())); }
-		if self.signature_section_size != 296 && self.signature_section_size != 0  { return Err(ErrorKind::MalformedData("Signature".to_string())); }
+		if self.signature != VPK_SIGNATURE { return Err(ErrorKind::MalformedData("Signature at offset 0".to_string())); }
+		if self.version != 2 { return Err(ErrorKind::MalformedData("Major Version at offset 4".to_string())); }
+		if self.other_md5_section_size != 48 { return Err(ErrorKind::MalformedData("OtherMD5 section size at offset 20".to_string())); }
 		Ok(())
 	}
 }
@@ -54,54 +53,3 @@ impl Default for HeaderV2 {
 		}
 	}
 }
-
-/// Represents the data structure following each filename in the VPK directory
-#[derive(Clone, Default, Serialize, Deserialize)]
-pub struct DirectoryEntryData {
-	/// A 32bit CRC of the file's data.
-	pub crc : u32,
-	/// The number of bytes contained in the index file.
-	pub preload_bytes_size : u16,
-	
-	/// A zero based index of the archive this file's data is contained in.
-	/// If 0x7fff, the data follows the directory.
-	pub archive_index : u16,
-
-	/// If ArchiveIndex is 0x7fff, the offset of the file data relative to the end of the directory (see the header for more details).
-	/// Otherwise, the offset of the data from the start of the specified archive.
-	pub data_offset : u32,
-
-	/// If zero, the entire file is stored in the preload data.
-	/// Otherwise, the number of bytes stored starting at EntryOffset.
-	pub data_length : u32,
-
-	/// Should always be 0xffff
-	pub terminator : u16,
-}
-
-impl DirectoryEntryData {
-	pub const SIZE : usize = 18;
-	pub const TERMINATOR : u16 = 0xffff;
-	pub const DATA_IN_DIRECTORY_ARCHIVE_INDEX : u16 = 0x7fff;
-
-	pub(super) fn total_data_size(&self) -> u32 {
-		self.data_length + u32::from(self.preload_bytes_size)
-	}
-
-	pub(super) fn is_valid(&self) -> Result<(), ErrorKind> {
-		if self.terminator != Self::TERMINATOR { return Err(ErrorKind::MalformedData("Directory Entry Terminator".to_string())) }
-		Ok(())
-	}
-
-	pub(super) fn is_in_directory_archive(&self) -> bool {
-		self.archive_index == Self::DATA_IN_DIRECTORY_ARCHIVE_INDEX
-	}
-
-	pub(super) fn is_preload_only(&self) -> bool {
-		self.data_length == 0
-	}
-
-	pub(super) fn has_preload(&self) -> bool {
-		self.preload_bytes_size != 0
-	}
-}
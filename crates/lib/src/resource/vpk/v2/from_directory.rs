@@ -0,0 +1,141 @@
+//! Creating VPKs from folders and lists of files on disk.
+
+use std::io::SeekFrom;
+use std::path::PathBuf;
+use std::io::prelude::*;
+use super::*;
+use create::{CreateOptions, EntryPrototype};
+
+/// A file which is only held open while it is being read.
+///
+/// Packing a folder creates an entry per file before any are written,
+/// opening them all up front could exceed the limit on open files.
+struct LazyFile {
+	path : PathBuf,
+	len : u64,
+	position : u64,
+	file : Option<File>,
+}
+
+impl LazyFile {
+	fn new(path : PathBuf) -> Result<Self, ErrorKind> {
+		let len = std::fs::metadata(&path)?.len();
+		Ok(LazyFile { path, len, position : 0, file : None })
+	}
+}
+
+impl Read for LazyFile {
+	fn read(&mut self, buf : &mut [u8]) -> std::io::Result<usize> {
+		if self.file.is_none() {
+			let mut file = File::open(&self.path)?;
+			file.seek(SeekFrom::Start(self.position))?;
+			self.file = Some(file);
+		}
+
+		let n = self.file.as_mut().unwrap().read(buf)?; /* Okay because of the open above */
+		self.position += u64::try_from(n).unwrap();
+		if n == 0 || self.position >= self.len { /* Close once fully read */
+			self.file = None;
+		}
+		Ok(n)
+	}
+}
+
+impl Seek for LazyFile {
+	/// Seeking only moves the position, the file is reopened at it by the next read.
+	fn seek(&mut self, pos : SeekFrom) -> std::io::Result<u64> {
+		let position = match pos {
+			SeekFrom::Start(n) => Some(n),
+			SeekFrom::End(n) => self.len.checked_add_signed(n),
+			SeekFrom::Current(n) => self.position.checked_add_signed(n),
+		}.ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position"))?;
+
+		if position != self.position {
+			self.file = None;
+			self.position = position;
+		}
+		Ok(position)
+	}
+}
+
+impl ReadSeek for LazyFile {}
+
+/// Recursively lists the files under `root`, as paths relative to it with `/` separators.
+///
+/// Symbolic links to files are followed, links to folders are not to avoid cycles.
+fn list_files(root : &Path) -> Result<Vec<String>, ErrorKind> {
+	let mut files = Vec::<String>::new();
+	let mut pending = vec![PathBuf::new()];
+
+	while let Some(relative) = pending.pop() {
+		for item in std::fs::read_dir(root.join(&relative))? {
+			let item = item?;
+			let path = relative.join(item.file_name());
+			let file_type = item.file_type()?;
+
+			if file_type.is_dir() {
+				pending.push(path);
+			} else if file_type.is_file() || (file_type.is_symlink() && item.path().is_file()) {
+				let path = path.to_str().ok_or_else(|| ErrorKind::MalformedData(format!("Path {} is not valid UTF-8", path.display())))?;
+				files.push(path.replace(std::path::MAIN_SEPARATOR, "/"));
+			}
+		}
+	}
+
+	files.sort_unstable();
+	Ok(files)
+}
+
+impl VPKv2 {
+	/// Creates an entry for every file under `source` which passes `filter`, in path order.
+	///
+	/// Entries are named by their path relative to `source`, see `EntryPrototypeV2::from_path`.
+	/// Their data is stored in the data archives without preload unless changed by `CreateOptionsV2::pack_rules`.
+	///
+	/// # Errors
+	/// * `MalformedData` - When a filter pattern is invalid or a path isn't valid UTF-8.
+	pub fn entries_from_directory(source : &Path, filter : &DirectoryFilter) -> Result<Vec<EntryPrototype>, ErrorKind> {
+		let matches = filter.compile()?;
+
+		let mut entries = Vec::<EntryPrototype>::new();
+		for path in list_files(source)? {
+			if !matches(&path) {
+				continue;
+			}
+			let data = LazyFile::new(source.join(&path))?;
+			entries.push(EntryPrototype::from_path(false, 0, &path, Box::new(data)));
+		}
+		Ok(entries)
+	}
+
+	/// Creates an entry for each file in a list, in the order given.
+	///
+	/// # Arguments
+	/// * `source` - The folder the paths are relative to.
+	/// * `paths` - Paths relative to `source` which become the entries' full paths, e.g. read from a response file.
+	///
+	/// # Errors
+	/// * `DoesNotExist` - When a listed file doesn't exist.
+	pub fn entries_from_paths(source : &Path, paths : &[String]) -> Result<Vec<EntryPrototype>, ErrorKind> {
+		paths.iter().map(|path| {
+			let file = source.join(path);
+			if !file.is_file() {
+				return Err(ErrorKind::DoesNotExist(file.display().to_string()));
+			}
+			Ok(EntryPrototype::from_path(false, 0, path, Box::new(LazyFile::new(file)?)))
+		}).collect()
+	}
+
+	/// Creates new VPK dir and data files containing the files under `source`.
+	///
+	/// # Arguments
+	/// * `source` - The folder to pack, it becomes the root of the VPK.
+	/// * `directory_path` - The directory to create the files in.
+	/// * `filename` - Base name for the VPKs, e.g. `hl2_misc` -> `hl2_misc_dir.vpk`
+	/// * `filter` - Selects which files are packed.
+	/// * `options` - Settings controlling the layout of the VPK, including the pack rules applied to each file.
+	pub fn create_from_directory(source : &Path, directory_path : &Path, filename : &str, filter : &DirectoryFilter, options : &CreateOptions) -> Result<create::CreateSummary, ErrorKind> {
+		let mut entries = Self::entries_from_directory(source, filter)?;
+		Self::create_with_options(directory_path, filename, &mut entries, options)
+	}
+}
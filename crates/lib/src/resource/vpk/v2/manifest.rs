@@ -0,0 +1,128 @@
+//! A serializable description of a VPK's structure, e.g. for build dashboards.
+//!
+//! The schema is versioned by `MANIFEST_SCHEMA_VERSION`, fields are only added or changed alongside an increase of it.
+//! Checksums and keys are lowercase hex strings.
+
+use serde::{Serialize, Deserialize};
+use super::*;
+
+/// The version of the manifest schema written to `Manifest::schema_version`.
+pub const MANIFEST_SCHEMA_VERSION : u32 = 1;
+
+/// Everything stored in a VPK's directory file apart from the entry data itself.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+	pub schema_version : u32,
+	/// The header of the directory file as read.
+	pub header : data::HeaderV2,
+	/// The number of data archives found alongside the directory file.
+	pub archive_count : usize,
+	/// Every entry, sorted by path.
+	pub entries : Vec<ManifestEntry>,
+	/// Archive checksums in the order stored.
+	pub archive_md5 : Vec<ManifestArchiveMD5>,
+	pub other_md5 : ManifestOtherMD5,
+	/// Present when the directory file is signed.
+	pub signature : Option<ManifestSignature>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+	/// Full path, e.g. `materials/example.vmt`.
+	pub path : String,
+	pub crc : u32,
+	/// Bytes stored in the directory tree.
+	pub preload_size : u16,
+	/// Index of the data archive holding the rest of the data, `0x7fff` when embedded in the directory file.
+	pub archive_index : u16,
+	/// Whether the data follows the directory tree rather than being in a data archive.
+	pub embedded : bool,
+	/// Offset of the data within its archive.
+	pub offset : u32,
+	/// Bytes stored in the archive, not including the preload.
+	pub length : u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestArchiveMD5 {
+	pub archive_index : u32,
+	pub starting_offset : u32,
+	pub count : u32,
+	pub md5 : String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestOtherMD5 {
+	/// MD5 of the directory tree.
+	pub tree_checksum : String,
+	/// MD5 of the archive MD5 section.
+	pub archive_md5_section_checksum : String,
+	/// Stored as found, its contents aren't known.
+	pub unknown : String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestSignature {
+	/// DER encoded public key.
+	pub public_key : String,
+	pub signature : String,
+}
+
+impl Manifest {
+	/// Serializes the manifest as pretty printed JSON.
+	pub fn to_json(&self) -> String {
+		serde_json::to_string_pretty(self).expect("Manifest can't be serialized")
+	}
+
+	/// Parses a manifest written by `to_json`.
+	///
+	/// # Errors
+	/// * `MalformedData` - When the JSON doesn't match the schema.
+	pub fn from_json(json : &str) -> Result<Self, ErrorKind> {
+		serde_json::from_str(json).map_err(|e| ErrorKind::MalformedData(format!("Manifest: {}", e)))
+	}
+}
+
+impl VPKv2 {
+	/// Describes the structure of this VPK, see `Manifest`.
+	pub fn manifest(&self) -> Manifest {
+		let mut entries : Vec<ManifestEntry> = self.directory.entries.iter().map(|handle| ManifestEntry {
+			path : handle.path.clone(),
+			crc : handle.entry.crc,
+			preload_size : handle.entry.preload_bytes_size,
+			archive_index : handle.entry.archive_index,
+			embedded : handle.entry.is_in_directory_archive(),
+			offset : handle.entry.data_offset,
+			length : handle.entry.data_length,
+		}).collect();
+		entries.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+
+		Manifest {
+			schema_version : MANIFEST_SCHEMA_VERSION,
+			header : self.raw_header.clone(),
+			archive_count : self.data.len(),
+			entries,
+			archive_md5 : self.archive_md5.iter().map(|c| ManifestArchiveMD5 {
+				archive_index : c.archive_index,
+				starting_offset : c.starting_offset,
+				count : c.count,
+				md5 : helpers::to_hex(&c.md5_checksum),
+			}).collect(),
+			other_md5 : ManifestOtherMD5 {
+				tree_checksum : helpers::to_hex(&self.other_md5.tree_checksum),
+				archive_md5_section_checksum : helpers::to_hex(&self.other_md5.archive_md5_section_checksum),
+				unknown : helpers::to_hex(&self.other_md5.unknown),
+			},
+			signature : self.signature.as_ref().map(|s| ManifestSignature {
+				public_key : helpers::to_hex(&s.public_key),
+				signature : helpers::to_hex(&s.signature),
+			}),
+		}
+	}
+}
+
+mod helpers {
+	pub(super) fn to_hex(bytes : &[u8]) -> String {
+		bytes.iter().map(|b| format!("{:02x}", b)).collect()
+	}
+}
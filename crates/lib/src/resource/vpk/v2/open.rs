@@ -3,46 +3,54 @@ use std::convert::TryInto;
 use super::*;
 
 impl VPKv2 {
-	fn open(dir : Reader, data : Vec<Reader>) -> Result<Self, ErrorKind> {
+	/// Opens a VPK from caller provided sources rather than files on disk.
+	/// 
+	/// # Arguments
+	/// * `dir` - The directory file, e.g. the contents of `pak01_dir.vpk`.
+	/// * `data` - Each data archive in index order, e.g. `pak01_000.vpk`, `pak01_001.vpk`...
+	/// 
+	/// Sources can be in memory buffers such as `Vec<u8>` or any `Read + Seek` wrapped in `ReadSeekAt`.
+	pub fn open_from_readers(dir : Reader, data : Vec<Reader>) -> Result<Self, ErrorKind> {
 		let header : data::HeaderV2 = {
-			let mut buf = [0u8; data::HeaderV2::SIZE];
-			dir.borrow_mut().seek(std::io::SeekFrom::Start(0))?;
-			dir.borrow_mut().read_exact(&mut buf)?;
+			let buf = helpers::read_dir_section(dir.as_ref(), 0, data::HeaderV2::SIZE.try_into().unwrap(), "Header")?;
 			bincode::deserialize(&buf)?
 		};
 
 		header.is_valid()?;
 
 		let directory = {
-			let mut buf = vec![0u8; header.tree_size.try_into().unwrap()];
-			dir.borrow_mut().seek(std::io::SeekFrom::Start(data::HeaderV2::SIZE.try_into().unwrap()))?;
-			dir.borrow_mut().read_exact(&mut buf)?;
+			let start = header.get_tree_start().try_into().unwrap();
+			let buf = helpers::read_dir_section(dir.as_ref(), start, header.tree_size.into(), "Directory tree")?;
 			directory::read_directory_tree(
 				buf.as_slice(),
-				header.get_tree_start().try_into().unwrap(), /* data::HeaderV2::SIZE.try_into().unwrap(), */
+				start,
 				header.get_data_start().try_into().unwrap()
 			)?
 		};
 
 		let archive_md5 = {
-			let start = data::HeaderV2::SIZE + ( header.tree_size + header.file_data_section_size) as usize;
-
-			let mut buf = vec![0u8; header.archive_md5_section_size.try_into().unwrap()];
-			dir.borrow_mut().seek(std::io::SeekFrom::Start(start.try_into().unwrap()))?;
-			dir.borrow_mut().read_exact(&mut buf)?;
-
-			helpers::read_section::<common_data::ArchiveMD5SectionEntry>(buf.as_slice())
+			let start = header.get_archive_md5_start().try_into().unwrap();
+			let buf = helpers::read_dir_section(dir.as_ref(), start, header.archive_md5_section_size.into(), "Archive MD5 section")?;
+			if buf.len() % common_data::ArchiveMD5SectionEntry::SIZE != 0 {
+				return Err(ErrorKind::MalformedData(format!("Archive MD5 section at offset {} is not a multiple of {} bytes", start, common_data::ArchiveMD5SectionEntry::SIZE)));
+			}
+			helpers::read_section::<common_data::ArchiveMD5SectionEntry>(buf.as_slice(), common_data::ArchiveMD5SectionEntry::SIZE)?
 		};
 
 		let other_md5 = {
-			let start = data::HeaderV2::SIZE + ( header.tree_size + header.file_data_section_size + header.archive_md5_section_size) as usize;
-
-			let mut buf = vec![0u8; header.other_md5_section_size.try_into().unwrap()];
-			dir.borrow_mut().seek(std::io::SeekFrom::Start(start.try_into().unwrap()))?;
-			dir.borrow_mut().read_exact(&mut buf)?;
+			let start = header.get_other_md5_start().try_into().unwrap();
+			let buf = helpers::read_dir_section(dir.as_ref(), start, header.other_md5_section_size.into(), "Other MD5 section")?;
 			crate::resource::read_from_bytes::<common_data::OtherMD5Section>(buf.as_slice())
 		};
 
+		let signature = if header.signature_section_size == 0 {
+			None
+		} else {
+			let start = header.get_signature_start().try_into().unwrap();
+			let buf = helpers::read_dir_section(dir.as_ref(), start, header.signature_section_size.into(), "Signature section")?;
+			Some(common_data::SignatureSection::from_bytes(buf.as_slice()).map_err(|_| ErrorKind::MalformedData(format!("Signature section at offset {}", start)))?)
+		};
+
 		Ok(VPKv2 {
 			raw_header : header,
 			dir,
@@ -50,33 +58,63 @@ impl VPKv2 {
 			directory,
 			archive_md5,
 			other_md5,
+			signature,
 		})
 	}
 }
 
-impl Open for VPKv2 {
-	fn open_from_path(path : &Path) -> Result<Self, crate::resource::error::ErrorKind> {
-		/* Open files */ 
-		let (dir_file, data_file) = {
-			let base_path = helpers::get_base_path(path);
-			
-			let dir_file : Reader = Rc::new(RefCell::new(Box::new(File::open(base_path.clone() + "dir.vpk")?)));
-			
-			let mut data_file = Vec::<Reader>::new();
-			let mut i = 0;
-			loop {
-				let s = base_path.clone() + &format!("{:0>3}", i).to_string() + ".vpk";
-				let p = std::path::Path::new(&s);
-				if !p.exists() {
-					break;
-				}
-				data_file.push(Rc::new(RefCell::new(Box::new(File::open(p)?))));
-				i += 1;
-			}
+impl VPKv2 {
+	/// Opens a VPK with the directory file and data archives mapped into memory.
+	/// 
+	/// Entries can then be borrowed without copying using `get_entry_bytes`.
+	/// 
+	/// # Safety
+	/// The files must not be modified by this or any other process while the VPK is open, see `memmap2::Mmap::map`.
+	pub unsafe fn open_from_path_mmap(path : &Path) -> Result<Self, ErrorKind> {
+		let (dir_file, data_file) = helpers::map_archive_files(path)?;
+		VPKv2::open_from_readers(dir_file, data_file)
+	}
 
-			(dir_file, data_file)
-		};
+	/// Borrows an entry's data directly from memory.
+	/// 
+	/// # Errors
+	/// * `Unsupported` - When the VPK is not held in memory, e.g. opened with `open_from_path`.
+	pub fn get_entry_bytes(&self, path : &str) -> Result<EntryBytes<'_>, ErrorKind> {
+		self.directory.get_entry_bytes(path, &self.dir, &self.data)
+	}
+
+	/// The checksums of ranges of archive data, one per entry or one per block depending on how the VPK was created.
+	pub fn archive_md5_entries(&self) -> &[common_data::ArchiveMD5SectionEntry] {
+		&self.archive_md5
+	}
+
+	/// The header of the directory file, containing the size of each section.
+	pub fn header(&self) -> &data::HeaderV2 {
+		&self.raw_header
+	}
+
+	/// The number of data archives opened alongside the directory file.
+	pub fn archive_count(&self) -> usize {
+		self.data.len()
+	}
+
+	/// Opens a VPK from exactly the given files, for sets which don't follow the `_dir.vpk`/`_NNN.vpk` naming convention.
+	/// 
+	/// # Arguments
+	/// * `dir_path` - The directory file.
+	/// * `data_paths` - Each data archive in index order.
+	/// 
+	/// # Errors
+	/// * `DoesNotExist` - When any of the files don't exist.
+	pub fn open_from_paths(dir_path : &Path, data_paths : &[std::path::PathBuf]) -> Result<Self, ErrorKind> {
+		let (dir_file, data_file) = helpers::open_files(dir_path, data_paths)?;
+		Self::open_from_readers(dir_file, data_file)
+	}
+}
 
-		VPKv2::open(dir_file, data_file)
+impl Open for VPKv2 {
+	fn open_from_path(path : &Path) -> Result<Self, crate::resource::error::ErrorKind> {
+		let (dir_file, data_file) = helpers::open_archive_files(path)?;
+		VPKv2::open_from_readers(dir_file, data_file)
 	}
 }
\ No newline at end of file
@@ -0,0 +1,103 @@
+//! Pack rules assigning entry settings by file pattern, like the control files used by Valve's packer.
+
+use serde::{Serialize, Deserialize};
+use super::*;
+
+/// Settings given to every entry whose path matches `pattern`.
+///
+/// Fields left as `None` are not changed by this rule.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PackRule {
+	/// Glob matched against the entry's full path, e.g. `*.vmt` or `sound/**/*.wav`.
+	///
+	/// `*` also matches across `/` so `*.vmt` matches at any depth.
+	pub pattern : String,
+	/// How many bytes from the start of the entry are stored as preload data, clamped to the entry's size.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub preload_size : Option<u16>,
+	/// Stores the entry's data in the directory file rather than a data archive.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub store_in_directory : Option<bool>,
+	/// Entries in the same group are written together and groups never share a data archive.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub group : Option<String>,
+}
+
+/// The settings for one entry after every matching rule is applied.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(super) struct RuleSettings {
+	pub(super) preload_size : Option<u16>,
+	pub(super) store_in_directory : Option<bool>,
+	pub(super) group : Option<String>,
+}
+
+/// An ordered list of `PackRule`s, later rules override earlier ones for the fields they set.
+///
+/// Loaded from JSON in the form
+/// ```json
+/// { "rules": [
+///     { "pattern": "*.vmt", "store_in_directory": true },
+///     { "pattern": "*.wav", "preload_size": 4096, "group": "sound" }
+/// ] }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct PackRules {
+	rules : Vec<(helpers::PathPattern, PackRule)>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PackRulesFile {
+	rules : Vec<PackRule>,
+}
+
+impl PackRules {
+	/// # Errors
+	/// * `MalformedData` - When a pattern isn't a valid glob.
+	pub fn new(rules : Vec<PackRule>) -> Result<Self, ErrorKind> {
+		let rules = rules.into_iter()
+			.map(|rule| Ok((helpers::PathPattern::new(&rule.pattern)?, rule)))
+			.collect::<Result<Vec<_>, ErrorKind>>()?;
+		Ok(PackRules { rules })
+	}
+
+	/// Parses rules from JSON, see `PackRules` for the format.
+	///
+	/// # Errors
+	/// * `MalformedData` - When the JSON or a pattern is invalid.
+	pub fn from_json(json : &str) -> Result<Self, ErrorKind> {
+		let file : PackRulesFile = serde_json::from_str(json).map_err(|e| ErrorKind::MalformedData(format!("Pack rules: {}", e)))?;
+		Self::new(file.rules)
+	}
+
+	/// Reads rules from a JSON config file, see `PackRules` for the format.
+	pub fn open(path : &Path) -> Result<Self, ErrorKind> {
+		Self::from_json(&std::fs::read_to_string(path)?)
+	}
+
+	/// Serializes the rules to JSON in the format read by `from_json`.
+	pub fn to_json(&self) -> String {
+		let file = PackRulesFile { rules : self.rules().cloned().collect() };
+		serde_json::to_string_pretty(&file).expect("Pack rules can't be serialized")
+	}
+
+	/// The rules in the order they are applied.
+	pub fn rules(&self) -> impl Iterator<Item = &PackRule> {
+		self.rules.iter().map(|(_, rule)| rule)
+	}
+
+	/// Combines every rule matching `path`.
+	pub(super) fn settings_for(&self, path : &str) -> RuleSettings {
+		let mut settings = RuleSettings::default();
+		for (pattern, rule) in &self.rules {
+			if !pattern.matches(path) {
+				continue;
+			}
+			if rule.preload_size.is_some() { settings.preload_size = rule.preload_size; }
+			if rule.store_in_directory.is_some() { settings.store_in_directory = rule.store_in_directory; }
+			if rule.group.is_some() { settings.group = rule.group.clone(); }
+		}
+		settings
+	}
+}
@@ -0,0 +1,228 @@
+//! Verification of the RSA signature found at the end of signed directory files.
+//!
+//! The signature is RSASSA-PKCS1-v1_5 using SHA-256 over every byte of the directory file preceding the signature section.
+//! The public key is stored as a DER encoded `SubjectPublicKeyInfo`. This is how `SigningKey` signs VPKs.
+//!
+//! The section follows the documented layout, but it hasn't been checked against a pak signed by Valve. Whether their signatures
+//! cover the same bytes with the same hash is unconfirmed, so they may not validate.
+
+use std::io::Write;
+use rsa::{RsaPrivateKey, RsaPublicKey, Pkcs1v15Sign, pkcs1::DecodeRsaPrivateKey, pkcs8::{DecodePrivateKey, DecodePublicKey, EncodePublicKey}, traits::PublicKeyParts};
+use sha2::{Sha256, Digest};
+use super::*;
+
+impl VPKv2 {
+	/// Returns the signature section if this VPK is signed.
+	pub fn signature(&self) -> Option<&common_data::SignatureSection> {
+		self.signature.as_ref()
+	}
+
+	/// Hashes every byte of the directory file which is covered by the signature.
+	fn signed_digest(&self) -> Result<Vec<u8>, ErrorKind> {
+		let end = u64::try_from(self.raw_header.get_signature_start()).unwrap();
+
+		let mut hasher = Sha256::new();
+		let mut buf = vec![0u8; 64 * 1024];
+		let mut position = 0u64;
+		while position < end {
+			let len = std::cmp::min(end - position, buf.len() as u64) as usize;
+			self.dir.read_exact_at(&mut buf[..len], position)?;
+			hasher.update(&buf[..len]);
+			position += len as u64;
+		}
+
+		Ok(hasher.finalize().to_vec())
+	}
+}
+
+impl ValidateSignature for VPKv2 {
+	fn validate_signature(&self) -> Result<(), ErrorKind> {
+		let signature = self.signature.as_ref().ok_or_else(|| ErrorKind::DoesNotExist("Signature".to_string()))?;
+
+		let key = RsaPublicKey::from_public_key_der(&signature.public_key)
+			.map_err(|_| ErrorKind::MalformedData("Signature Public Key".to_string()))?;
+
+		self.verify_signature(&key, signature)
+	}
+
+	fn validate_signature_with_key(&self, public_key : &[u8]) -> Result<(), ErrorKind> {
+		let signature = self.signature.as_ref().ok_or_else(|| ErrorKind::DoesNotExist("Signature".to_string()))?;
+
+		let key = RsaPublicKey::from_public_key_der(public_key)
+			.map_err(|_| ErrorKind::MalformedData("Trusted Public Key".to_string()))?;
+		if RsaPublicKey::from_public_key_der(&signature.public_key).ok().as_ref() != Some(&key) {
+			return Err(ErrorKind::ValidationFailed("signature public key is not the trusted key".to_string()));
+		}
+
+		self.verify_signature(&key, signature)
+	}
+}
+
+impl VPKv2 {
+	fn verify_signature(&self, key : &RsaPublicKey, signature : &common_data::SignatureSection) -> Result<(), ErrorKind> {
+		key.verify(Pkcs1v15Sign::new::<Sha256>(), &self.signed_digest()?, &signature.signature)
+			.map_err(|_| ErrorKind::ValidationFailed("signature".to_string()))
+	}
+}
+
+/// Reads a PEM encoded public key (`BEGIN PUBLIC KEY`) for `ValidateSignature::validate_signature_with_key`.
+/// 
+/// # Returns
+/// The DER encoded public key.
+/// 
+/// # Errors
+/// * `MalformedData` - When the key cannot be decoded.
+pub fn public_key_from_pem(public_key : &str) -> Result<Vec<u8>, ErrorKind> {
+	RsaPublicKey::from_public_key_pem(public_key)
+		.ok()
+		.and_then(|key| key.to_public_key_der().ok())
+		.map(|der| der.as_bytes().to_vec())
+		.ok_or_else(|| ErrorKind::MalformedData("Trusted Public Key".to_string()))
+}
+
+/// Reads a `.publickey.vdf` file written by `vpk.exe generate_keypair` for `ValidateSignature::validate_signature_with_key`.
+/// 
+/// # Returns
+/// The DER encoded public key.
+/// 
+/// # Errors
+/// * `MalformedData` - When the file has no `rsa_public_key` or it cannot be decoded.
+pub fn public_key_from_valve_key_file(public_key : &str) -> Result<Vec<u8>, ErrorKind> {
+	let der = helpers::read_keyvalues_hex(public_key, "rsa_public_key")?;
+	RsaPublicKey::from_public_key_der(&der).map_err(|_| ErrorKind::MalformedData("Trusted Public Key".to_string()))?;
+	Ok(der)
+}
+
+/// An RSA key pair used to sign VPKs during creation.
+#[derive(Clone)]
+pub struct SigningKey {
+	private_key : RsaPrivateKey,
+	/// DER encoded `SubjectPublicKeyInfo` written to the signature section
+	public_key : Vec<u8>,
+}
+
+impl std::fmt::Debug for SigningKey {
+	fn fmt(&self, f : &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("SigningKey").field("public_key", &self.public_key).finish_non_exhaustive()
+	}
+}
+
+impl SigningKey {
+	fn new(private_key : RsaPrivateKey) -> Result<Self, ErrorKind> {
+		let public_key = RsaPublicKey::from(&private_key).to_public_key_der()
+			.map_err(|_| ErrorKind::MalformedData("Signing Public Key".to_string()))?
+			.as_bytes()
+			.to_vec();
+		Ok(Self { private_key, public_key })
+	}
+
+	/// Reads a PEM encoded private key, either PKCS#8 (`BEGIN PRIVATE KEY`) or PKCS#1 (`BEGIN RSA PRIVATE KEY`).
+	/// 
+	/// The public key is derived from the private key.
+	/// 
+	/// # Errors
+	/// * `MalformedData` - When the key cannot be decoded.
+	pub fn from_pem(private_key : &str) -> Result<Self, ErrorKind> {
+		let key = RsaPrivateKey::from_pkcs8_pem(private_key)
+			.or_else(|_| RsaPrivateKey::from_pkcs1_pem(private_key))
+			.map_err(|_| ErrorKind::MalformedData("Signing Private Key".to_string()))?;
+		Self::new(key)
+	}
+
+	/// Reads a key pair in the KeyValues format written by `vpk.exe generate_keypair`.
+	/// 
+	/// # Arguments
+	/// * `private_key` - Contents of the `.privatekey.vdf` file, containing a hex encoded `rsa_private_key`.
+	/// * `public_key` - Contents of the `.publickey.vdf` file, containing a hex encoded `rsa_public_key`.
+	/// 
+	/// # Errors
+	/// * `MalformedData` - When either key cannot be decoded or they do not belong to the same pair.
+	pub fn from_valve_key_files(private_key : &str, public_key : &str) -> Result<Self, ErrorKind> {
+		let private_der = helpers::read_keyvalues_hex(private_key, "rsa_private_key")?;
+		let public_der = helpers::read_keyvalues_hex(public_key, "rsa_public_key")?;
+
+		let key = RsaPrivateKey::from_pkcs8_der(&private_der)
+			.or_else(|_| RsaPrivateKey::from_pkcs1_der(&private_der))
+			.map_err(|_| ErrorKind::MalformedData("Signing Private Key".to_string()))?;
+		let public = RsaPublicKey::from_public_key_der(&public_der)
+			.map_err(|_| ErrorKind::MalformedData("Signing Public Key".to_string()))?;
+		if public != RsaPublicKey::from(&key) {
+			return Err(ErrorKind::MalformedData("Public key does not match the private key".to_string()));
+		}
+
+		Ok(Self { private_key : key, public_key : public_der })
+	}
+
+	/// The DER encoded public key written to the signature section.
+	pub fn public_key(&self) -> &[u8] {
+		&self.public_key
+	}
+
+	/// The size of the signature section this key produces.
+	pub(super) fn section_size(&self) -> usize {
+		8 + self.public_key.len() + self.private_key.size()
+	}
+
+	/// Signs a SHA-256 digest of the directory file, returning the complete signature section.
+	pub(super) fn sign(&self, digest : &[u8]) -> Result<common_data::SignatureSection, ErrorKind> {
+		let signature = self.private_key.sign(Pkcs1v15Sign::new::<Sha256>(), digest)
+			.map_err(|e| ErrorKind::MalformedData(format!("Signing failed: {}", e)))?;
+		Ok(common_data::SignatureSection { public_key : self.public_key.clone(), signature })
+	}
+}
+
+/// Passes writes through to `inner`, hashing them for signing when a key is given.
+pub(super) struct SigningWriter<'a, W : Write> {
+	inner : W,
+	key : Option<&'a SigningKey>,
+	hasher : Sha256,
+}
+
+impl<'a, W : Write> SigningWriter<'a, W> {
+	pub(super) fn new(inner : W, key : Option<&'a SigningKey>) -> Self {
+		Self { inner, key, hasher : Sha256::new() }
+	}
+
+	/// Appends the signature section covering everything written so far, if there is a key.
+	pub(super) fn finish(mut self) -> Result<(), ErrorKind> {
+		if let Some(key) = self.key {
+			let section = key.sign(&self.hasher.finalize())?;
+			self.inner.write_all(&section.to_bytes())?;
+		}
+		self.inner.flush()?;
+		Ok(())
+	}
+}
+
+impl<'a, W : Write> Write for SigningWriter<'a, W> {
+	fn write(&mut self, buf : &[u8]) -> std::io::Result<usize> {
+		let n = self.inner.write(buf)?;
+		if self.key.is_some() {
+			self.hasher.update(&buf[..n]);
+		}
+		Ok(n)
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		self.inner.flush()
+	}
+}
+
+mod helpers {
+	use super::*;
+
+	/// Finds the quoted value following the quoted `key` in a KeyValues document and decodes it from hex.
+	pub(super) fn read_keyvalues_hex(document : &str, key : &str) -> Result<Vec<u8>, ErrorKind> {
+		let tokens : Vec<&str> = document.split('"').skip(1).step_by(2).collect();
+		let value = tokens.windows(2)
+			.find(|w| w[0].eq_ignore_ascii_case(key))
+			.map(|w| w[1])
+			.ok_or_else(|| ErrorKind::MalformedData(format!("Key file is missing \"{}\"", key)))?;
+
+		if value.len() % 2 != 0 { return Err(ErrorKind::MalformedData(format!("\"{}\" is not valid hex", key))); }
+		(0..value.len()).step_by(2)
+			.map(|i| u8::from_str_radix(value.get(i..i + 2).unwrap_or("?"), 16))
+			.collect::<Result<Vec<u8>, _>>()
+			.map_err(|_| ErrorKind::MalformedData(format!("\"{}\" is not valid hex", key)))
+	}
+}
@@ -1,6 +1,7 @@
 use std::{fs::File, path::PathBuf};
 
 use valve_resource_tools::resource::vpk::VPKv2;
+use valve_resource_tools::resource::vpk::v2::EntryPrototypeV2;
 use valve_resource_tools::resource::vpk::prelude::*;
 
 pub fn get_test_data_path() -> String {
@@ -27,7 +28,12 @@ pub fn get_tmp_dir() -> PathBuf {
 	return tmp_dir;
 }
 
-pub fn do_vecs_match<T: PartialEq>(a: &Vec<T>, b: &Vec<T>) -> bool {
+pub fn do_vecs_match<T: PartialEq>(a: &[T], b: &[T]) -> bool {
 	let matching = a.iter().zip(b.iter()).filter(|&(a, b)| a == b).count();
 	matching == a.len() && matching == b.len()
+}
+
+/// An entry at `path/filename.txt` holding the example data file `data`.
+pub fn example_entry(path : &str, filename : &str, store_in_directory : bool, preload_size : u16, data : &str) -> EntryPrototypeV2 {
+	EntryPrototypeV2::new(store_in_directory, preload_size, path.to_string(), filename.to_string(), "txt".to_string(), Box::new(get_example_data(data)))
 }
\ No newline at end of file
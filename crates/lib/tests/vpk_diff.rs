@@ -0,0 +1,127 @@
+mod common;
+use common::*;
+
+use valve_resource_tools::resource::vpk::{self, VPKv2, DiffOptions, ChangeKind};
+use valve_resource_tools::resource::vpk::v2::*;
+use valve_resource_tools::resource::vpk::prelude::*;
+
+#[test]
+/// Compares two builds with an entry of each kind of change.
+fn diff_changes() {
+	let old_dir = get_tmp_dir();
+	let mut ents = vec![
+		example_entry("diff", "a", false, 0, "ArchiveOnly.txt"),
+		example_entry("diff", "b", false, 0, "EmbededArchiveOnly.txt"),
+		example_entry("diff", "c", false, 21, "PreloadAndArchive.txt"),
+		example_entry("diff", "e", false, 26, "PreloadOnly.txt"),
+	];
+	VPKv2::create_with_options(&old_dir, "pak01", &mut ents, &CreateOptionsV2 { chunk_size : 40, ..Default::default() }).unwrap();
+
+	let new_dir = get_tmp_dir();
+	let mut ents = vec![
+		/* Unchanged */ example_entry("diff", "a", false, 0, "ArchiveOnly.txt"),
+		/* Moved from archive 2 to 0 */ example_entry("diff", "c", false, 21, "PreloadAndArchive.txt"),
+		/* Added */ example_entry("diff", "d", false, 0, "EmbededArchiveOnly.txt"),
+		/* Modified */ example_entry("diff", "e", false, 0, "ArchiveOnly.txt"),
+	];
+	VPKv2::create(&new_dir, "pak01", &mut ents).unwrap();
+
+	let old = VPKv2::open_from_path(&old_dir.join("pak01_dir.vpk")).unwrap();
+	let new = VPKv2::open_from_path(&new_dir.join("pak01_dir.vpk")).unwrap();
+	let diff = vpk::diff(&old, &new, &DiffOptions::default()).unwrap();
+
+	let changes : Vec<(&str, ChangeKind)> = diff.changes.iter().map(|c| (c.path.as_str(), c.kind)).collect();
+	assert_eq!(changes, vec![
+		("diff/b.txt", ChangeKind::Removed),
+		("diff/c.txt", ChangeKind::Moved),
+		("diff/d.txt", ChangeKind::Added),
+		("diff/e.txt", ChangeKind::Modified),
+	]);
+	assert_eq!(diff.unchanged, 1);
+	assert_eq!(diff.count(ChangeKind::Moved), 1);
+
+	let moved = &diff.changes[1];
+	assert_eq!((moved.old.as_ref().unwrap().archive_index, moved.new.as_ref().unwrap().archive_index), (2, 0));
+	assert!(diff.changes[0].new.is_none() && diff.changes[2].old.is_none());
+
+	assert!(vpk::diff(&new, &new, &DiffOptions { compare_data : true }).unwrap().is_empty());
+
+	std::fs::remove_dir_all(old_dir).unwrap();
+	std::fs::remove_dir_all(new_dir).unwrap();
+}
+
+#[test]
+/// VPKs of different versions holding the same files compare equal by their data.
+fn diff_versions() {
+	let v1 = vpk::open(&get_example_path("vpk_v1_test_dir.vpk")).unwrap();
+	let v2 = open_test_vpk();
+
+	let diff = vpk::diff(&v1, &v2, &DiffOptions { compare_data : true }).unwrap();
+	assert!(diff.is_empty(), "{:?}", diff.changes);
+	assert_eq!(diff.unchanged, 4);
+
+	/* The V2 fixture was written without CRCs so its entries are compared by data */
+	let diff = vpk::diff(&v1, &v2, &DiffOptions::default()).unwrap();
+	assert!(diff.is_empty(), "{:?}", diff.changes);
+}
+
+#[test]
+/// Entries of VPKs written without CRCs still show as modified when their data changes but not their size.
+fn diff_without_crcs() {
+	let old_dir = get_tmp_dir();
+	let new_dir = get_tmp_dir();
+	for dir in [&old_dir, &new_dir] {
+		for name in ["vpk_test_dir.vpk", "vpk_test_000.vpk"] {
+			std::fs::copy(get_example_path(name), dir.join(name)).unwrap();
+		}
+	}
+	/* "ArchiveOnly.txt" is at the start of the data archive */
+	let mut data = std::fs::read(new_dir.join("vpk_test_000.vpk")).unwrap();
+	data[0] ^= 0xff;
+	std::fs::write(new_dir.join("vpk_test_000.vpk"), data).unwrap();
+
+	let old = VPKv2::open_from_path(&old_dir.join("vpk_test_dir.vpk")).unwrap();
+	let new = VPKv2::open_from_path(&new_dir.join("vpk_test_dir.vpk")).unwrap();
+	assert_eq!(old.get_handle_from_path("testing-folder/ArchiveOnly.txt").unwrap().crc(), 0);
+	let diff = vpk::diff(&old, &new, &DiffOptions::default()).unwrap();
+
+	let changes : Vec<(&str, ChangeKind)> = diff.changes.iter().map(|c| (c.path.as_str(), c.kind)).collect();
+	assert_eq!(changes, vec![("testing-folder/ArchiveOnly.txt", ChangeKind::Modified)]);
+	assert_eq!(diff.unchanged, 3);
+
+	std::fs::remove_dir_all(old_dir).unwrap();
+	std::fs::remove_dir_all(new_dir).unwrap();
+}
+
+#[test]
+/// Entries only moved within their data archive or stored with another preload size aren't moved.
+fn diff_relocations() {
+	let old_dir = get_tmp_dir();
+	let mut ents = vec![
+		example_entry("diff", "a", false, 0, "ArchiveOnly.txt"),
+		example_entry("diff", "b", false, 0, "EmbededArchiveOnly.txt"),
+		example_entry("diff", "c", false, 21, "PreloadAndArchive.txt"),
+		example_entry("diff", "d", false, 26, "PreloadOnly.txt"),
+	];
+	VPKv2::create(&old_dir, "pak01", &mut ents).unwrap();
+
+	let new_dir = get_tmp_dir();
+	let mut ents = vec![
+		/* Offset changes within archive 0 */ example_entry("diff", "b", false, 0, "EmbededArchiveOnly.txt"),
+		/* Preload size changes */ example_entry("diff", "c", false, 0, "PreloadAndArchive.txt"),
+		/* Preload only in both */ example_entry("diff", "d", true, 26, "PreloadOnly.txt"),
+		/* Moved from archive 0 to the directory file */ example_entry("diff", "a", true, 0, "ArchiveOnly.txt"),
+	];
+	VPKv2::create(&new_dir, "pak01", &mut ents).unwrap();
+
+	let old = VPKv2::open_from_path(&old_dir.join("pak01_dir.vpk")).unwrap();
+	let new = VPKv2::open_from_path(&new_dir.join("pak01_dir.vpk")).unwrap();
+	let diff = vpk::diff(&old, &new, &DiffOptions::default()).unwrap();
+
+	let changes : Vec<(&str, ChangeKind)> = diff.changes.iter().map(|c| (c.path.as_str(), c.kind)).collect();
+	assert_eq!(changes, vec![("diff/a.txt", ChangeKind::Moved)]);
+	assert_eq!(diff.unchanged, 3);
+
+	std::fs::remove_dir_all(old_dir).unwrap();
+	std::fs::remove_dir_all(new_dir).unwrap();
+}
@@ -0,0 +1,94 @@
+mod common;
+use common::*;
+
+use valve_resource_tools::resource::error::ErrorKind;
+use valve_resource_tools::resource::vpk::{self, DirectoryFilter, ExtractSummary};
+use valve_resource_tools::resource::vpk::prelude::*;
+
+const ENTRIES : [&str; 4] = ["PreloadOnly.txt", "PreloadAndArchive.txt", "ArchiveOnly.txt", "EmbededArchiveOnly.txt"];
+
+#[test]
+/// Extracts every entry of both versions and compares them to the original files.
+fn extract_all() {
+	for name in ["vpk_test_dir.vpk", "vpk_v1_test_dir.vpk"] {
+		let vpk = vpk::open(&get_example_path(name)).unwrap();
+		let tmp_dir = get_tmp_dir();
+
+		let mut seen = Vec::<String>::new();
+		let summary = vpk.extract_all(&tmp_dir.join("out"), &DirectoryFilter::default(), |p| {
+			assert_eq!(p.entries_done, seen.len() + 1);
+			assert_eq!(p.entries_total, 4);
+			assert_eq!(p.bytes_total, 114);
+			seen.push(p.path.to_string());
+			true
+		}).unwrap();
+		assert_eq!(summary, ExtractSummary { entries : 4, bytes : 114, cancelled : false });
+
+		/* Archive data is read in offset order with embedded data after the data archives */
+		assert_eq!(seen.iter().position(|p| p.ends_with("/ArchiveOnly.txt")).unwrap() + 1, seen.iter().position(|p| p.ends_with("/PreloadAndArchive.txt")).unwrap());
+		assert!(seen.last().unwrap().ends_with("/EmbededArchiveOnly.txt"));
+
+		for f in ENTRIES {
+			let extracted = std::fs::read(tmp_dir.join("out/testing-folder").join(f)).unwrap();
+			let original = std::fs::read(get_example_path(f)).unwrap();
+			if !do_vecs_match(&extracted, &original) { panic!("Extracted \"{}\" from {} does not match original content", f, name) }
+		}
+
+		std::fs::remove_dir_all(tmp_dir).unwrap();
+	}
+}
+
+#[test]
+fn extract_filtered() {
+	let vpk = open_test_vpk();
+	let tmp_dir = get_tmp_dir();
+
+	let filter = DirectoryFilter { include : vec!["*Archive*".to_string()], exclude : vec!["*/Embeded*".to_string()] };
+	let summary = vpk.extract_all(&tmp_dir, &filter, |_| true).unwrap();
+	assert_eq!(summary.entries, 2);
+	assert!(tmp_dir.join("testing-folder/ArchiveOnly.txt").is_file());
+	assert!(tmp_dir.join("testing-folder/PreloadAndArchive.txt").is_file());
+	assert!(!tmp_dir.join("testing-folder/EmbededArchiveOnly.txt").exists());
+	assert!(!tmp_dir.join("testing-folder/PreloadOnly.txt").exists());
+
+	let invalid = DirectoryFilter { include : vec!["[".to_string()], ..Default::default() };
+	assert!(matches!(vpk.extract_all(&tmp_dir, &invalid, |_| true), Err(ErrorKind::MalformedData(_))));
+
+	std::fs::remove_dir_all(tmp_dir).unwrap();
+}
+
+#[test]
+fn extract_cancelled() {
+	let vpk = open_test_vpk();
+	let tmp_dir = get_tmp_dir();
+
+	let summary = vpk.extract_all(&tmp_dir, &DirectoryFilter::default(), |p| p.entries_done < 2).unwrap();
+	assert_eq!(summary.entries, 2);
+	assert!(summary.cancelled);
+	let written = std::fs::read_dir(tmp_dir.join("testing-folder")).unwrap().count();
+	assert_eq!(written, 2);
+
+	std::fs::remove_dir_all(tmp_dir).unwrap();
+}
+
+#[test]
+/// Entries with paths leading outside of the target must not be written.
+fn extract_unsafe_path() {
+	use valve_resource_tools::resource::vpk::VPKv2;
+	use valve_resource_tools::resource::vpk::v2::*;
+
+	let tmp_dir = get_tmp_dir();
+	let mut ents = vec![
+		EntryPrototypeV2::from_path(false, 0, "safe.txt", Box::new(get_example_data("ArchiveOnly.txt"))),
+		EntryPrototypeV2::from_path(false, 0, "../escaped.txt", Box::new(get_example_data("ArchiveOnly.txt"))),
+	];
+	VPKv2::create(&tmp_dir, "unsafe", &mut ents).unwrap();
+	let vpk = VPKv2::open_from_path(&tmp_dir.join("unsafe_dir.vpk")).unwrap();
+
+	let target = tmp_dir.join("out");
+	assert!(matches!(vpk.extract_all(&target, &DirectoryFilter::default(), |_| true), Err(ErrorKind::MalformedData(_))));
+	assert!(!target.exists());
+	assert!(!tmp_dir.join("escaped.txt").exists());
+
+	std::fs::remove_dir_all(tmp_dir).unwrap();
+}
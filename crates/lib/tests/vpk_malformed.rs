@@ -0,0 +1,105 @@
+mod common;
+use common::*;
+
+use std::sync::Arc;
+
+use valve_resource_tools::resource::error::ErrorKind;
+use valve_resource_tools::resource::vpk::{VPKv1, VPKv2, Reader};
+use valve_resource_tools::resource::vpk::prelude::*;
+
+fn open_v2(dir : Vec<u8>) -> Result<VPKv2, ErrorKind> {
+	let dir : Reader = Arc::new(dir);
+	VPKv2::open_from_readers(dir.clone(), vec![dir])
+}
+
+/// Reads and validates everything an opened VPK claims to hold, which must fail with errors rather than panic.
+fn read_everything(vpk : &VPKv2) {
+	for handle in vpk.get_handles() {
+		let _ = vpk.get_entry_bytes(handle.path());
+		if let Ok(mut reader) = vpk.get_entry_from_path(handle.path()) {
+			let _ = std::io::copy(&mut reader, &mut std::io::sink());
+		}
+	}
+	let _ = vpk.validate_archive();
+	let _ = vpk.validate_other();
+	let _ = vpk.validate_entries();
+	let _ = vpk.validate_signature();
+}
+
+fn assert_malformed_at<T>(result : Result<T, ErrorKind>, offset : usize) {
+	match result {
+		Err(ErrorKind::MalformedData(e)) => assert!(e.contains(&format!("offset {}", offset)), "{}", e),
+		Err(e) => panic!("Expected MalformedData at offset {}, got {}", offset, e),
+		Ok(_) => panic!("Expected MalformedData at offset {}", offset),
+	}
+}
+
+#[test]
+/// Every truncation of the directory files fails to open with `MalformedData`.
+fn truncated() {
+	for name in ["vpk_test_dir.vpk", "vpk_signed_dir.vpk"] {
+		let dir = std::fs::read(get_example_path(name)).unwrap();
+		for len in 0..dir.len() {
+			match open_v2(dir[..len].to_vec()) {
+				Err(ErrorKind::MalformedData(_)) => {},
+				Err(e) => panic!("{} truncated to {} bytes: {}", name, len, e),
+				Ok(_) => panic!("{} truncated to {} bytes opened", name, len),
+			}
+		}
+		assert!(open_v2(dir).is_ok());
+	}
+
+	let dir = std::fs::read(get_example_path("vpk_v1_test_dir.vpk")).unwrap();
+	for len in 0..12 + 202 {
+		let dir : Reader = Arc::new(dir[..len].to_vec());
+		assert!(matches!(VPKv1::open_from_readers(dir, Vec::new()), Err(ErrorKind::MalformedData(_))));
+	}
+}
+
+#[test]
+/// Corrupting any single byte of a directory file never panics, whether or not it still opens.
+fn corrupted() {
+	let dir = std::fs::read(get_example_path("vpk_signed_dir.vpk")).unwrap();
+	for i in 0..dir.len() {
+		for value in [0x00, 0x01, 0x7f, 0x80, 0xff, dir[i] ^ 0xff] {
+			let mut corrupt = dir.clone();
+			corrupt[i] = value;
+			if let Ok(vpk) = open_v2(corrupt) {
+				read_everything(&vpk);
+			}
+		}
+	}
+}
+
+#[test]
+/// Sizes and strings reaching past their bounds are reported with the offset they were read from.
+fn malformed_offsets() {
+	let dir = std::fs::read(get_example_path("vpk_test_dir.vpk")).unwrap();
+	let set_u32 = |dir : &mut Vec<u8>, at : usize, value : u32| dir[at..at + 4].copy_from_slice(&value.to_le_bytes());
+
+	/* Tree size larger than the file */
+	let mut corrupt = dir.clone();
+	set_u32(&mut corrupt, 8, u32::MAX);
+	assert_malformed_at(open_v2(corrupt), 28);
+
+	/* Tree cut off part way through the first extension */
+	let mut corrupt = dir.clone();
+	set_u32(&mut corrupt, 8, 2);
+	assert_malformed_at(open_v2(corrupt), 28);
+
+	/* Preload size past the end of the tree, the first entry follows "txt\0testing-folder\0EmbededArchiveOnly\0" */
+	let entry = 28 + 4 + 15 + 19;
+	let mut corrupt = dir.clone();
+	corrupt[entry + 4..entry + 6].copy_from_slice(&u16::MAX.to_le_bytes());
+	assert_malformed_at(open_v2(corrupt), entry + 18);
+
+	/* Missing terminator */
+	let mut corrupt = dir.clone();
+	corrupt[entry + 16] = 0;
+	assert_malformed_at(open_v2(corrupt), entry);
+
+	/* Archive MD5 section which isn't a whole number of entries */
+	let mut corrupt = dir;
+	set_u32(&mut corrupt, 16, 27);
+	assert_malformed_at(open_v2(corrupt), 28 + 202 + 28);
+}
@@ -0,0 +1,45 @@
+use std::io::Read;
+
+mod common;
+use common::*;
+
+#[test]
+fn open() {
+	use valve_resource_tools::resource::vpk::v1::*;
+	use valve_resource_tools::resource::vpk::prelude::*;
+
+	VPKv1::open_from_path(std::path::Path::new(&get_example_path("vpk_v1_test_dir.vpk"))).unwrap();
+}
+
+#[test]
+/// Reads every entry type from a V1 archive and compares them to the original files.
+fn open_read_every_entry_permutation() {
+	use valve_resource_tools::resource::vpk::v1::*;
+	use valve_resource_tools::resource::vpk::prelude::*;
+
+	let vpk = VPKv1::open_from_path(std::path::Path::new(&get_example_path("vpk_v1_test_dir.vpk"))).unwrap();
+
+	for f in &[
+		"PreloadOnly.txt",
+		"PreloadAndArchive.txt",
+		"ArchiveOnly.txt",
+		"EmbededArchiveOnly.txt",
+	] {
+		let path = "testing-folder/".to_owned() + f;
+		let mut buf = Vec::<u8>::new();
+		let mut res = Vec::<u8>::new();
+		vpk.get_entry_from_path(&path).unwrap_or_else(|_| panic!("{} does not exist", path))
+			.read_to_end(&mut buf).unwrap_or_else(|_| panic!("Couldn't read whole entry at \"{}\"", path));
+		get_example_data(f).read_to_end(&mut res).expect("Couldn't read example data");
+		if !do_vecs_match(&buf, &res) { panic!("File entry \"{}\" does not match original content", path) }
+	}
+}
+
+#[test]
+fn validate_entries() {
+	use valve_resource_tools::resource::vpk::v1::*;
+	use valve_resource_tools::resource::vpk::prelude::*;
+
+	let vpk = VPKv1::open_from_path(std::path::Path::new(&get_example_path("vpk_v1_test_dir.vpk"))).unwrap();
+	assert!(vpk.validate_entries().unwrap().is_empty());
+}
@@ -48,8 +48,636 @@ fn create_with_every_entry_permutation() {
 	}
 }
 
-/* Tests TODO:
- * Single large file (>maximum split)
- * Single large file (>data_offset)
- * 0 Length File
- */
\ No newline at end of file
+#[test]
+/// Creates a vpk with empty entries right where a data archive is full, checking they don't start a new archive.
+fn create_zero_length_entries() {
+	use valve_resource_tools::resource::vpk::v2::*;
+	use valve_resource_tools::resource::vpk::prelude::*;
+
+	let empty = || Box::new(std::io::Cursor::new(&[][..]));
+	let tmp_dir = get_tmp_dir();
+	let mut ents = vec![
+		/* 18 bytes, filling archive 0 */ EntryPrototypeV2::new(false, 0, "empty".to_string(), "ArchiveOnly".to_string(), "txt".to_string(), Box::new(get_example_data("ArchiveOnly.txt"))),
+		EntryPrototypeV2::new(false, 0, "empty".to_string(), "archive".to_string(),  "txt".to_string(), empty()),
+		EntryPrototypeV2::new(true,  0, "empty".to_string(), "embedded".to_string(), "txt".to_string(), empty()),
+		/* 28 bytes, starting archive 1 */ EntryPrototypeV2::new(false, 0, "empty".to_string(), "EmbededArchiveOnly".to_string(), "txt".to_string(), Box::new(get_example_data("EmbededArchiveOnly.txt"))),
+	];
+	let options = CreateOptionsV2 { chunk_size : 18, ..Default::default() };
+	VPKv2::create_with_options(&tmp_dir, "vpk_test", &mut ents, &options).expect("Create failed");
+
+	assert_eq!(std::fs::metadata(tmp_dir.join("vpk_test_000.vpk")).unwrap().len(), 18);
+	assert_eq!(std::fs::metadata(tmp_dir.join("vpk_test_001.vpk")).unwrap().len(), 28);
+	assert!(!tmp_dir.join("vpk_test_002.vpk").exists());
+
+	let vpk = VPKv2::open_from_path(&tmp_dir.join("vpk_test_dir.vpk")).expect("Couldn't open VPK");
+	if !vpk.validate_archive().is_empty() { panic!("Validation failed")}
+	vpk.validate_other().expect("Other validation section failed");
+	assert!(vpk.validate_entries().unwrap().is_empty());
+	for path in &["empty/archive.txt", "empty/embedded.txt"] {
+		let handle = vpk.get_handle_from_path(path).unwrap();
+		assert_eq!((handle.total_size(), handle.archive_size()), (0, 0));
+		let mut buf = Vec::<u8>::new();
+		vpk.get_entry_from_path(path).unwrap().read_to_end(&mut buf).unwrap();
+		assert!(buf.is_empty());
+	}
+	assert_eq!(vpk.get_handle_from_path("empty/EmbededArchiveOnly.txt").unwrap().archive_index(), 1);
+
+	std::fs::remove_dir_all(tmp_dir).unwrap();
+}
+
+#[test]
+/// Appends entries to an existing vpk and checks both the old and new entries are intact.
+fn append_entries() {
+	use valve_resource_tools::resource::vpk::v2::*;
+	use valve_resource_tools::resource::vpk::prelude::*;
+
+	let tmp_dir = get_tmp_dir();
+	let mut ents = vec![
+		EntryPrototypeV2::new(false, 21, "old".to_string(), "PreloadAndArchive".to_string(),  "txt".to_string(), Box::new(get_example_data("PreloadAndArchive.txt"))),
+		EntryPrototypeV2::new(true,   0, "old".to_string(), "EmbededArchiveOnly".to_string(), "txt".to_string(), Box::new(get_example_data("EmbededArchiveOnly.txt"))),
+	];
+	VPKv2::create(&tmp_dir, "vpk_test", &mut ents).expect("Create failed");
+
+	let mut ents = vec![
+		EntryPrototypeV2::new(false,  0, "new".to_string(), "ArchiveOnly".to_string(),        "txt".to_string(), Box::new(get_example_data("ArchiveOnly.txt"))),
+		EntryPrototypeV2::new(true,   0, "new".to_string(), "EmbededArchiveOnly".to_string(), "txt".to_string(), Box::new(get_example_data("EmbededArchiveOnly.txt"))),
+	];
+	VPKv2::append_entries(&tmp_dir, "vpk_test", &mut ents).expect("Append failed");
+
+	/* Appending an existing path must fail */ {
+		let mut ents = vec![
+			EntryPrototypeV2::new(false, 0, "old".to_string(), "PreloadAndArchive".to_string(), "txt".to_string(), Box::new(get_example_data("PreloadAndArchive.txt"))),
+		];
+		assert!(VPKv2::append_entries(&tmp_dir, "vpk_test", &mut ents).is_err());
+	}
+
+	let vpk = VPKv2::open_from_path(&tmp_dir.join("vpk_test_dir.vpk")).expect("Couldn't open VPK");
+	if !vpk.validate_archive().is_empty() { panic!("Validation failed")}
+	vpk.validate_other().expect("Other validation section failed");
+	assert_eq!(vpk.list_entries().len(), 4);
+
+	for (path, f) in &[
+		("old/PreloadAndArchive.txt",  "PreloadAndArchive.txt"),
+		("old/EmbededArchiveOnly.txt", "EmbededArchiveOnly.txt"),
+		("new/ArchiveOnly.txt",        "ArchiveOnly.txt"),
+		("new/EmbededArchiveOnly.txt", "EmbededArchiveOnly.txt"),
+	] {
+		let mut buf = Vec::<u8>::new();
+		let mut res = Vec::<u8>::new();
+		vpk.get_entry_from_path(path).unwrap_or_else(|_| panic!("{} does not exist", path))
+			.read_to_end(&mut buf).unwrap_or_else(|_| panic!("Couldn't read whole entry at \"{}\"", path));
+		get_example_data(f).read_to_end(&mut res).expect("Couldn't read example data");
+		if !do_vecs_match(&buf, &res) { panic!("File entry \"{}\" does not match original content", path) }
+	}
+
+	std::fs::remove_dir_all(tmp_dir).unwrap();
+}
+
+#[test]
+/// Fails part way through an append, checking the data files and directory are left as they were.
+fn append_entries_rolls_back() {
+	use std::io::{Read, Seek, SeekFrom};
+	use valve_resource_tools::resource::vpk::ReadSeek;
+	use valve_resource_tools::resource::vpk::v2::*;
+	use valve_resource_tools::resource::vpk::prelude::*;
+
+	/// 18 bytes which can't be read.
+	struct Unreadable;
+	impl Read for Unreadable {
+		fn read(&mut self, _ : &mut [u8]) -> std::io::Result<usize> { Err(std::io::Error::other("Unreadable")) }
+	}
+	impl Seek for Unreadable {
+		fn seek(&mut self, pos : SeekFrom) -> std::io::Result<u64> { Ok(if let SeekFrom::End(0) = pos { 18 } else { 0 }) }
+	}
+	impl ReadSeek for Unreadable {}
+
+	let tmp_dir = get_tmp_dir();
+	let mut ents = vec![
+		EntryPrototypeV2::new(false, 0, "old".to_string(), "ArchiveOnly".to_string(), "txt".to_string(), Box::new(get_example_data("ArchiveOnly.txt"))),
+	];
+	VPKv2::create_with_options(&tmp_dir, "vpk_test", &mut ents, &CreateOptionsV2 { chunk_size : 40, ..Default::default() }).expect("Create failed");
+	let original_dir = std::fs::read(tmp_dir.join("vpk_test_dir.vpk")).unwrap();
+	let original_data = std::fs::read(tmp_dir.join("vpk_test_000.vpk")).unwrap();
+
+	/* The first entry is added to archive 0, the second starts archive 1 and the third fails */
+	let mut ents = vec![
+		EntryPrototypeV2::new(false, 0, "new".to_string(), "ArchiveOnly".to_string(),        "txt".to_string(), Box::new(get_example_data("ArchiveOnly.txt"))),
+		EntryPrototypeV2::new(false, 0, "new".to_string(), "EmbededArchiveOnly".to_string(), "txt".to_string(), Box::new(get_example_data("EmbededArchiveOnly.txt"))),
+		EntryPrototypeV2::new(false, 0, "new".to_string(), "Unreadable".to_string(),         "txt".to_string(), Box::new(Unreadable)),
+	];
+	let options = CreateOptionsV2 { chunk_size : 40, ..Default::default() };
+	assert!(VPKv2::append_entries_with_options(&tmp_dir, "vpk_test", &mut ents, &options).is_err());
+
+	assert_eq!(std::fs::read(tmp_dir.join("vpk_test_dir.vpk")).unwrap(), original_dir);
+	assert_eq!(std::fs::read(tmp_dir.join("vpk_test_000.vpk")).unwrap(), original_data);
+	assert!(!tmp_dir.join("vpk_test_001.vpk").exists() && !tmp_dir.join("vpk_test_dir.vpk.tmp").exists());
+	assert!(VPKv2::open_from_path(&tmp_dir.join("vpk_test_dir.vpk")).unwrap().validate_archive().is_empty());
+
+	std::fs::remove_dir_all(tmp_dir).unwrap();
+}
+
+#[test]
+/// Corrupts a data file and checks the CRC of only the affected entry fails.
+fn validate_entries_crc() {
+	use std::io::{Seek, SeekFrom, Write};
+	use valve_resource_tools::resource::vpk::v2::*;
+	use valve_resource_tools::resource::vpk::prelude::*;
+
+	let tmp_dir = get_tmp_dir();
+	let mut ents = vec![
+		EntryPrototypeV2::new(false, 26, "crc".to_string(), "PreloadOnly".to_string(),        "txt".to_string(), Box::new(get_example_data("PreloadOnly.txt"))),
+		EntryPrototypeV2::new(false,  0, "crc".to_string(), "ArchiveOnly".to_string(),        "txt".to_string(), Box::new(get_example_data("ArchiveOnly.txt"))),
+		EntryPrototypeV2::new(true,   0, "crc".to_string(), "EmbededArchiveOnly".to_string(), "txt".to_string(), Box::new(get_example_data("EmbededArchiveOnly.txt"))),
+		EntryPrototypeV2::new(false, 21, "crc".to_string(), "PreloadAndArchive".to_string(),  "txt".to_string(), Box::new(get_example_data("PreloadAndArchive.txt"))),
+	];
+	VPKv2::create(&tmp_dir, "vpk_test", &mut ents).expect("Create failed");
+
+	let path = tmp_dir.join("vpk_test_dir.vpk");
+	let vpk = VPKv2::open_from_path(&path).expect("Couldn't open VPK");
+	assert!(vpk.validate_entries().unwrap().is_empty());
+	let mut buf = Vec::<u8>::new();
+	vpk.get_entry_from_path("crc/ArchiveOnly.txt").unwrap().with_crc_check().read_to_end(&mut buf).expect("CRC check failed on valid data");
+	drop(vpk);
+
+	/* ArchiveOnly.txt is the first entry in the data file */ {
+		let mut data = std::fs::OpenOptions::new().write(true).open(tmp_dir.join("vpk_test_000.vpk")).unwrap();
+		data.seek(SeekFrom::Start(0)).unwrap();
+		data.write_all(b"X").unwrap();
+	}
+
+	let vpk = VPKv2::open_from_path(&path).expect("Couldn't open VPK");
+	assert_eq!(vpk.validate_entries().unwrap(), vec!["crc/ArchiveOnly.txt"]);
+	assert!(!vpk.validate_entry("crc/ArchiveOnly.txt").unwrap());
+	assert!(vpk.validate_entry("crc/PreloadAndArchive.txt").unwrap());
+	let mut buf = Vec::<u8>::new();
+	assert!(vpk.get_entry_from_path("crc/ArchiveOnly.txt").unwrap().with_crc_check().read_to_end(&mut buf).is_err());
+
+	std::fs::remove_dir_all(tmp_dir).unwrap();
+}
+
+#[test]
+/// Creates a vpk with entries larger than the block size data is copied in.
+fn create_large_entries() {
+	use valve_resource_tools::resource::vpk::v2::*;
+	use valve_resource_tools::resource::vpk::prelude::*;
+
+	let tmp_dir = get_tmp_dir();
+	let content : Vec<u8> = (0..300_000).map(|_| rand::random::<u8>()).collect();
+	let source = tmp_dir.join("large.bin");
+	std::fs::write(&source, &content).unwrap();
+
+	let mut ents = vec![
+		EntryPrototypeV2::new(false, 1000, "large".to_string(), "archive".to_string(),  "bin".to_string(), Box::new(std::fs::File::open(&source).unwrap())),
+		EntryPrototypeV2::new(true,  1000, "large".to_string(), "embedded".to_string(), "bin".to_string(), Box::new(std::fs::File::open(&source).unwrap())),
+	];
+	VPKv2::create(&tmp_dir, "vpk_test", &mut ents).expect("Create failed");
+
+	let vpk = VPKv2::open_from_path(&tmp_dir.join("vpk_test_dir.vpk")).expect("Couldn't open VPK");
+	if !vpk.validate_archive().is_empty() { panic!("Validation failed")}
+	vpk.validate_other().expect("Other validation section failed");
+	assert!(vpk.validate_entries().unwrap().is_empty());
+	for path in &["large/archive.bin", "large/embedded.bin"] {
+		let mut buf = Vec::<u8>::new();
+		vpk.get_entry_from_path(path).unwrap().read_to_end(&mut buf).unwrap();
+		if !do_vecs_match(&buf, &content) { panic!("File entry \"{}\" does not match original content", path) }
+	}
+
+	std::fs::remove_dir_all(tmp_dir).unwrap();
+}
+
+#[test]
+/// Creates a vpk with a tiny chunk size to check entries start new archives instead of overflowing them.
+fn create_with_chunk_size() {
+	use valve_resource_tools::resource::vpk::v2::*;
+	use valve_resource_tools::resource::vpk::prelude::*;
+
+	let tmp_dir = get_tmp_dir();
+	let mut ents = vec![
+		/* 18 bytes */ EntryPrototypeV2::new(false,  0, "chunks".to_string(), "ArchiveOnly".to_string(),        "txt".to_string(), Box::new(get_example_data("ArchiveOnly.txt"))),
+		/* 28 bytes */ EntryPrototypeV2::new(false,  0, "chunks".to_string(), "EmbededArchiveOnly".to_string(), "txt".to_string(), Box::new(get_example_data("EmbededArchiveOnly.txt"))),
+		/* 21 bytes */ EntryPrototypeV2::new(false, 21, "chunks".to_string(), "PreloadAndArchive".to_string(),  "txt".to_string(), Box::new(get_example_data("PreloadAndArchive.txt"))),
+	];
+	let options = CreateOptionsV2 { chunk_size : 40, ..Default::default() };
+	VPKv2::create_with_options(&tmp_dir, "vpk_test", &mut ents, &options).expect("Create failed");
+
+	/* The second entry doesn't fit after the first, the third fits after the second */
+	assert_eq!(std::fs::metadata(tmp_dir.join("vpk_test_000.vpk")).unwrap().len(), 18);
+	assert_eq!(std::fs::metadata(tmp_dir.join("vpk_test_001.vpk")).unwrap().len(), 28);
+	assert_eq!(std::fs::metadata(tmp_dir.join("vpk_test_002.vpk")).unwrap().len(), 21);
+
+	let vpk = VPKv2::open_from_path(&tmp_dir.join("vpk_test_dir.vpk")).expect("Couldn't open VPK");
+	if !vpk.validate_archive().is_empty() { panic!("Validation failed")}
+	assert!(vpk.validate_entries().unwrap().is_empty());
+	assert_eq!(vpk.get_handle_from_path("chunks/PreloadAndArchive.txt").unwrap().archive_index(), 2);
+
+	std::fs::remove_dir_all(tmp_dir).unwrap();
+}
+
+/// Pretends to be a file too large to fit in a VPK without needing the space for it.
+struct OversizedData;
+
+impl std::io::Read for OversizedData {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		buf.fill(0);
+		Ok(buf.len())
+	}
+}
+
+impl std::io::Seek for OversizedData {
+	fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+		match pos {
+			std::io::SeekFrom::End(_) => Ok(u64::from(u32::MAX) + 1),
+			_ => Ok(0),
+		}
+	}
+}
+
+impl valve_resource_tools::resource::vpk::ReadSeek for OversizedData {}
+
+#[test]
+/// Entries which can't be represented in the format should error rather than panic.
+fn create_oversized_entry() {
+	use valve_resource_tools::resource::error::ErrorKind;
+	use valve_resource_tools::resource::vpk::v2::*;
+
+	let tmp_dir = get_tmp_dir();
+	let mut ents = vec![
+		EntryPrototypeV2::new(false, 0, "large".to_string(), "oversized".to_string(), "bin".to_string(), Box::new(OversizedData)),
+	];
+	assert!(matches!(VPKv2::create(&tmp_dir, "vpk_test", &mut ents), Err(ErrorKind::TooLarge(_))));
+
+	let options = CreateOptionsV2 { chunk_size : u64::from(u32::MAX) + 1, ..Default::default() };
+	assert!(matches!(VPKv2::create_with_options(&tmp_dir, "vpk_test", &mut [], &options), Err(ErrorKind::TooLarge(_))));
+
+	std::fs::remove_dir_all(tmp_dir).unwrap();
+}
+
+#[test]
+/// Creates the same set of entries in different orders and checks the output is identical.
+fn create_reproducible() {
+	use valve_resource_tools::resource::vpk::v2::*;
+
+	let entries = || vec![
+		EntryPrototypeV2::new(false, 26, "a".to_string(),   "PreloadOnly".to_string(),        "txt".to_string(), Box::new(get_example_data("PreloadOnly.txt"))),
+		EntryPrototypeV2::new(false,  0, "b".to_string(),   "ArchiveOnly".to_string(),        "txt".to_string(), Box::new(get_example_data("ArchiveOnly.txt"))),
+		EntryPrototypeV2::new(true,   0, "c/d".to_string(), "EmbededArchiveOnly".to_string(), "txt".to_string(), Box::new(get_example_data("EmbededArchiveOnly.txt"))),
+		EntryPrototypeV2::new(false, 21, "c".to_string(),   "PreloadAndArchive".to_string(),  "cfg".to_string(), Box::new(get_example_data("PreloadAndArchive.txt"))),
+		EntryPrototypeV2::new(false,  0, "a".to_string(),   "ArchiveOnly".to_string(),        "txt".to_string(), Box::new(get_example_data("ArchiveOnly.txt"))),
+	];
+	let options = CreateOptionsV2 { reproducible : true, ..Default::default() };
+
+	let first = get_tmp_dir();
+	VPKv2::create_with_options(&first, "vpk_test", &mut entries(), &options).expect("Create failed");
+
+	let second = get_tmp_dir();
+	let mut reversed = entries();
+	reversed.reverse();
+	VPKv2::create_with_options(&second, "vpk_test", &mut reversed, &options).expect("Create failed");
+
+	for f in &["vpk_test_dir.vpk", "vpk_test_000.vpk"] {
+		let a = std::fs::read(first.join(f)).unwrap();
+		let b = std::fs::read(second.join(f)).unwrap();
+		if !do_vecs_match(&a, &b) { panic!("{} differs between builds", f) }
+	}
+
+	std::fs::remove_dir_all(first).unwrap();
+	std::fs::remove_dir_all(second).unwrap();
+}
+
+#[test]
+/// Creates a standalone VPK and checks every entry is embedded with no data archives written.
+fn create_single_file() {
+	use valve_resource_tools::resource::vpk::v2::*;
+	use valve_resource_tools::resource::vpk::prelude::*;
+
+	let tmp_dir = get_tmp_dir();
+	let mut ents = vec![
+		EntryPrototypeV2::new(false, 26, "addon".to_string(), "PreloadOnly".to_string(),       "txt".to_string(), Box::new(get_example_data("PreloadOnly.txt"))),
+		EntryPrototypeV2::new(false,  0, "addon".to_string(), "ArchiveOnly".to_string(),       "txt".to_string(), Box::new(get_example_data("ArchiveOnly.txt"))),
+		EntryPrototypeV2::new(false, 21, "addon".to_string(), "PreloadAndArchive".to_string(), "txt".to_string(), Box::new(get_example_data("PreloadAndArchive.txt"))),
+	];
+	let options = CreateOptionsV2 { single_file : true, ..Default::default() };
+	VPKv2::create_with_options(&tmp_dir, "addon", &mut ents, &options).expect("Create failed");
+
+	let mut extra = [
+		EntryPrototypeV2::new(false, 0, "addon".to_string(), "EmbededArchiveOnly".to_string(), "txt".to_string(), Box::new(get_example_data("EmbededArchiveOnly.txt"))),
+	];
+	VPKv2::append_entries_with_options(&tmp_dir, "addon", &mut extra, &options).expect("Append failed");
+
+	let files : Vec<String> = std::fs::read_dir(&tmp_dir).unwrap().map(|f| f.unwrap().file_name().into_string().unwrap()).collect();
+	assert_eq!(files, vec!["addon.vpk"]);
+
+	let vpk = VPKv2::open_from_path(&tmp_dir.join("addon.vpk")).expect("Couldn't open VPK");
+	assert!(vpk.validate_entries().unwrap().is_empty());
+	assert!(vpk.validate_archive().is_empty());
+	vpk.validate_other().unwrap();
+	for handle in vpk.get_handles() {
+		assert!(handle.is_embedded() || handle.is_preload_only(), "{} is not embedded", handle.path());
+	}
+	assert_eq!(vpk.list_entries().len(), 4);
+
+	std::fs::remove_dir_all(tmp_dir).unwrap();
+}
+
+#[test]
+/// Packs a folder, including files without a path or extension, and checks the filter is applied.
+fn create_from_directory() {
+	use valve_resource_tools::resource::vpk::v2::*;
+	use valve_resource_tools::resource::vpk::prelude::*;
+
+	let source = get_tmp_dir();
+	let files = [
+		("addoninfo.txt", "\"AddonInfo\" {}"),
+		("materials/models/example.vmt", "\"LightmappedGeneric\" {}"),
+		("bin/README", "no extension"),
+		("scripts/.gitignore", "*.tmp"),
+		("scripts/cache.tmp", "excluded"),
+		("notes.md", "not included"),
+	];
+	for (path, content) in &files {
+		let path = source.join(path);
+		std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+		std::fs::write(path, content).unwrap();
+	}
+
+	let filter = DirectoryFilter {
+		include : vec!["*.txt".to_string(), "materials/**".to_string(), "bin/*".to_string(), "scripts/*".to_string()],
+		exclude : vec!["*.tmp".to_string()],
+	};
+	let tmp_dir = get_tmp_dir();
+	VPKv2::create_from_directory(&source, &tmp_dir, "folder", &filter, &CreateOptionsV2::default()).expect("Create failed");
+
+	/* Appending rewrites the existing tree, which must keep the placeholders intact */
+	let mut extra = [EntryPrototypeV2::from_path(true, 0, "LICENSE", Box::new(std::io::Cursor::new(&b"license"[..])))];
+	VPKv2::append_entries(&tmp_dir, "folder", &mut extra).expect("Append failed");
+
+	let vpk = VPKv2::open_from_path(&tmp_dir.join("folder_dir.vpk")).expect("Couldn't open VPK");
+	let mut paths = vpk.list_entries();
+	paths.sort_unstable();
+	assert_eq!(paths, vec!["LICENSE", "addoninfo.txt", "bin/README", "materials/models/example.vmt", "scripts/.gitignore"]);
+	assert!(vpk.validate_entries().unwrap().is_empty());
+
+	for (path, content) in &files[..4] {
+		let mut buf = String::new();
+		vpk.get_entry_from_path(path).unwrap().read_to_string(&mut buf).unwrap();
+		assert_eq!(&buf, content);
+	}
+
+	assert!(VPKv2::entries_from_directory(&source, &DirectoryFilter { include : vec!["[".to_string()], ..Default::default() }).is_err());
+
+	std::fs::remove_dir_all(source).unwrap();
+	std::fs::remove_dir_all(tmp_dir).unwrap();
+}
+
+#[test]
+/// Packs a file list with rules loaded from JSON and checks each entry's settings and archive.
+fn create_with_pack_rules() {
+	use valve_resource_tools::resource::vpk::v2::*;
+	use valve_resource_tools::resource::vpk::prelude::*;
+
+	let source = get_tmp_dir();
+	let files = [
+		("materials/a.vmt", "\"LightmappedGeneric\" {}"),
+		("materials/b.vmt", "\"VertexLitGeneric\" {}"),
+		("models/example.mdl", "IDST model data"),
+		("sound/loud.wav", "RIFF loud sound data"),
+		("sound/quiet.wav", "RIFF"),
+	];
+	for (path, content) in &files {
+		let path = source.join(path);
+		std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+		std::fs::write(path, content).unwrap();
+	}
+
+	let rules = PackRules::from_json(r#"{ "rules": [
+		{ "pattern": "*.vmt", "store_in_directory": true },
+		{ "pattern": "*.wav", "preload_size": 8, "group": "sound" },
+		{ "pattern": "sound/quiet.wav", "preload_size": 100 }
+	] }"#).unwrap();
+	assert_eq!(PackRules::from_json(&rules.to_json()).unwrap().rules().collect::<Vec<_>>(), rules.rules().collect::<Vec<_>>());
+
+	/* Reversed so groups have to be gathered together */
+	let paths : Vec<String> = files.iter().rev().map(|(p, _)| p.to_string()).collect();
+	let mut entries = VPKv2::entries_from_paths(&source, &paths).unwrap();
+	let options = CreateOptionsV2 { pack_rules : Some(rules), ..Default::default() };
+	let tmp_dir = get_tmp_dir();
+	VPKv2::create_with_options(&tmp_dir, "rules", &mut entries, &options).expect("Create failed");
+
+	let vpk = VPKv2::open_from_path(&tmp_dir.join("rules_dir.vpk")).expect("Couldn't open VPK");
+	assert!(vpk.validate_entries().unwrap().is_empty());
+	assert!(vpk.validate_archive().is_empty());
+
+	assert!(vpk.get_handle_from_path("materials/a.vmt").unwrap().is_embedded());
+	assert!(vpk.get_handle_from_path("materials/b.vmt").unwrap().is_embedded());
+	let model = vpk.get_handle_from_path("models/example.mdl").unwrap();
+	let loud = vpk.get_handle_from_path("sound/loud.wav").unwrap();
+	let quiet = vpk.get_handle_from_path("sound/quiet.wav").unwrap();
+	assert_eq!(model.preload_size(), 0);
+	assert_eq!(loud.preload_size(), 8);
+	assert!(quiet.is_preload_only());
+	assert_eq!(quiet.preload_size(), 4);
+	assert_ne!(model.archive_index(), loud.archive_index());
+
+	for (path, content) in &files {
+		let mut buf = String::new();
+		vpk.get_entry_from_path(path).unwrap().read_to_string(&mut buf).unwrap();
+		assert_eq!(&buf, content);
+	}
+
+	/* The last archive holds the sound group so appended entries outside it go to a new archive */ {
+		std::fs::write(source.join("models/other.mdl"), "IDST other model data").unwrap();
+		std::fs::write(source.join("sound/new.wav"), "RIFF new sound data").unwrap();
+		let mut extra = VPKv2::entries_from_paths(&source, &["models/other.mdl".to_string(), "sound/new.wav".to_string()]).unwrap();
+		VPKv2::append_entries_with_options(&tmp_dir, "rules", &mut extra, &options).expect("Append failed");
+
+		let vpk = VPKv2::open_from_path(&tmp_dir.join("rules_dir.vpk")).expect("Couldn't open VPK");
+		assert!(vpk.validate_entries().unwrap().is_empty());
+		let other = vpk.get_handle_from_path("models/other.mdl").unwrap().archive_index();
+		assert_ne!(other, vpk.get_handle_from_path("sound/loud.wav").unwrap().archive_index());
+		assert_ne!(other, vpk.get_handle_from_path("sound/new.wav").unwrap().archive_index());
+	}
+
+	assert!(PackRules::from_json(r#"{ "rules": [ { "pattern": "*.vmt", "preload": 1 } ] }"#).is_err());
+	assert!(VPKv2::entries_from_paths(&source, &["missing.txt".to_string()]).is_err());
+
+	std::fs::remove_dir_all(source).unwrap();
+	std::fs::remove_dir_all(tmp_dir).unwrap();
+}
+
+#[test]
+/// Creates and appends with block checksums, checking the blocks cover every archive and validate.
+fn create_with_archive_md5_blocks() {
+	use valve_resource_tools::resource::vpk::v2::*;
+	use valve_resource_tools::resource::vpk::prelude::*;
+
+	let tmp_dir = get_tmp_dir();
+	let mut ents = vec![
+		/* 18 bytes */ EntryPrototypeV2::new(false,  0, "blocks".to_string(), "ArchiveOnly".to_string(),        "txt".to_string(), Box::new(get_example_data("ArchiveOnly.txt"))),
+		/* 28 bytes */ EntryPrototypeV2::new(false,  0, "blocks".to_string(), "EmbededArchiveOnly".to_string(), "txt".to_string(), Box::new(get_example_data("EmbededArchiveOnly.txt"))),
+		/* 21 bytes */ EntryPrototypeV2::new(false, 21, "blocks".to_string(), "PreloadAndArchive".to_string(),  "txt".to_string(), Box::new(get_example_data("PreloadAndArchive.txt"))),
+	];
+	let options = CreateOptionsV2 { chunk_size : 40, archive_md5_block_size : Some(16), ..Default::default() };
+	VPKv2::create_with_options(&tmp_dir, "vpk_test", &mut ents, &options).expect("Create failed");
+
+	/* Fits in the end of the last archive, continuing its partial block */
+	let mut extra = [EntryPrototypeV2::new(false, 0, "blocks".to_string(), "Appended".to_string(), "txt".to_string(), Box::new(get_example_data("ArchiveOnly.txt")))];
+	VPKv2::append_entries_with_options(&tmp_dir, "vpk_test", &mut extra, &options).expect("Append failed");
+
+	let vpk = VPKv2::open_from_path(&tmp_dir.join("vpk_test_dir.vpk")).expect("Couldn't open VPK");
+	assert!(vpk.validate_archive().is_empty());
+	assert!(vpk.validate_entries().unwrap().is_empty());
+
+	let mut blocks : Vec<(u32, u32, u32)> = vpk.archive_md5_entries().iter().map(|c| (c.archive_index, c.starting_offset, c.count)).collect();
+	blocks.sort_unstable();
+	assert_eq!(blocks, vec![
+		(0, 0, 16), (0, 16, 2),
+		(1, 0, 16), (1, 16, 12),
+		(2, 0, 16), (2, 16, 16), (2, 32, 7),
+	]);
+
+	/* Valve's block size gives one checksum per archive here */
+	let tmp_valve = get_tmp_dir();
+	let mut ents = vec![EntryPrototypeV2::new(false, 0, "blocks".to_string(), "ArchiveOnly".to_string(), "txt".to_string(), Box::new(get_example_data("ArchiveOnly.txt")))];
+	let options = CreateOptionsV2 { archive_md5_block_size : Some(VALVE_ARCHIVE_MD5_BLOCK_SIZE), ..Default::default() };
+	VPKv2::create_with_options(&tmp_valve, "vpk_test", &mut ents, &options).expect("Create failed");
+	let vpk = VPKv2::open_from_path(&tmp_valve.join("vpk_test_dir.vpk")).unwrap();
+	assert_eq!(vpk.archive_md5_entries().len(), 1);
+
+	/* Corrupted data fails validation */
+	let mut data = std::fs::read(tmp_dir.join("vpk_test_002.vpk")).unwrap();
+	data[20] ^= 0xff;
+	std::fs::write(tmp_dir.join("vpk_test_002.vpk"), data).unwrap();
+	let vpk = VPKv2::open_from_path(&tmp_dir.join("vpk_test_dir.vpk")).unwrap();
+	assert_eq!(vpk.validate_archive().len(), 1);
+
+	assert!(VPKv2::create_with_options(&tmp_valve, "zero", &mut [], &CreateOptionsV2 { archive_md5_block_size : Some(0), ..Default::default() }).is_err());
+
+	std::fs::remove_dir_all(tmp_dir).unwrap();
+	std::fs::remove_dir_all(tmp_valve).unwrap();
+}
+
+#[test]
+/// Rebuilds with one changed and one new entry, checking existing archives are left as they are and new data goes after them.
+fn rebuild_keeps_unchanged_archives() {
+	use valve_resource_tools::resource::vpk::v2::*;
+	use valve_resource_tools::resource::vpk::prelude::*;
+
+	let options = CreateOptionsV2 { chunk_size : 40, ..Default::default() };
+
+	let tmp_dir = get_tmp_dir();
+	let mut ents = vec![
+		/* 18 bytes */ example_entry("rebuild", "a", false, 0, "ArchiveOnly.txt"),
+		/* 28 bytes */ example_entry("rebuild", "b", false, 0, "EmbededArchiveOnly.txt"),
+		/* 21 bytes */ example_entry("rebuild", "c", false, 21, "PreloadAndArchive.txt"),
+	];
+	VPKv2::create_with_options(&tmp_dir, "vpk_test", &mut ents, &options).expect("Create failed");
+	let original : Vec<Vec<u8>> = (0..3).map(|i| std::fs::read(tmp_dir.join(format!("vpk_test_{:0>3}.vpk", i))).unwrap()).collect();
+
+	/* "b" changes and "d" is new, both go after the existing archives and "d" doesn't fit after "b" so it starts another */
+	let mut ents = vec![
+		example_entry("rebuild", "a", false, 0, "ArchiveOnly.txt"),
+		example_entry("rebuild", "b", false, 0, "PreloadOnly.txt"),
+		example_entry("rebuild", "c", false, 21, "PreloadAndArchive.txt"),
+		example_entry("rebuild", "d", false, 0, "ArchiveOnly.txt"),
+	];
+	let summary = VPKv2::rebuild(&tmp_dir, "vpk_test", &mut ents, &options).expect("Rebuild failed");
+	/* Archive 1 only holds the old data of "b" */
+	assert_eq!(summary, RebuildSummaryV2 { kept_archives : vec![0, 2], written_archives : vec![3, 4], orphaned_archives : vec![1], removed_archives : vec![] });
+	for (i, data) in original.iter().enumerate() {
+		assert_eq!(&std::fs::read(tmp_dir.join(format!("vpk_test_{:0>3}.vpk", i))).unwrap(), data);
+	}
+	assert!(!tmp_dir.join("vpk_test_003.vpk.tmp").exists() && !tmp_dir.join("vpk_test_dir.vpk.tmp").exists());
+
+	let vpk = VPKv2::open_from_path(&tmp_dir.join("vpk_test_dir.vpk")).expect("Couldn't open VPK");
+	assert!(vpk.validate_archive().is_empty());
+	assert!(vpk.validate_entries().unwrap().is_empty());
+	for (path, data) in [("rebuild/a.txt", "ArchiveOnly.txt"), ("rebuild/b.txt", "PreloadOnly.txt"), ("rebuild/c.txt", "PreloadAndArchive.txt"), ("rebuild/d.txt", "ArchiveOnly.txt")] {
+		let mut buf = Vec::new();
+		vpk.get_entry_from_path(path).unwrap().read_to_end(&mut buf).unwrap();
+		let mut content = Vec::new();
+		get_example_data(data).read_to_end(&mut content).unwrap();
+		if !do_vecs_match(&buf, &content) { panic!("File entry \"{}\" does not match original content", path) }
+	}
+	drop(vpk);
+
+	/* Removing every entry after "a" leaves only its archive */
+	let summary = VPKv2::rebuild(&tmp_dir, "vpk_test", &mut [example_entry("rebuild", "a", false, 0, "ArchiveOnly.txt")], &options).expect("Rebuild failed");
+	assert_eq!(summary, RebuildSummaryV2 { kept_archives : vec![0], written_archives : vec![], orphaned_archives : vec![], removed_archives : vec![1, 2, 3, 4] });
+	assert!(!tmp_dir.join("vpk_test_001.vpk").exists());
+	let vpk = VPKv2::open_from_path(&tmp_dir.join("vpk_test_dir.vpk")).expect("Couldn't open VPK");
+	assert_eq!(vpk.list_entries().len(), 1);
+	assert!(vpk.validate_archive().is_empty());
+
+	std::fs::remove_dir_all(tmp_dir).unwrap();
+}
+
+#[test]
+/// Rebuilds a VPK written without CRCs, checking the entries reused from it gain correct CRCs.
+fn rebuild_computes_missing_crcs() {
+	use valve_resource_tools::resource::vpk::v2::*;
+	use valve_resource_tools::resource::vpk::prelude::*;
+
+	let tmp_dir = get_tmp_dir();
+	for name in ["vpk_test_dir.vpk", "vpk_test_000.vpk"] {
+		std::fs::copy(get_example_path(name), tmp_dir.join(name)).unwrap();
+	}
+	assert_eq!(VPKv2::open_from_path(&tmp_dir.join("vpk_test_dir.vpk")).unwrap().validate_entries().unwrap().len(), 4);
+
+	let mut ents = vec![
+		EntryPrototypeV2::new(false,  0, "testing-folder".to_string(), "ArchiveOnly".to_string(),       "txt".to_string(), Box::new(get_example_data("ArchiveOnly.txt"))),
+		EntryPrototypeV2::new(false, 21, "testing-folder".to_string(), "PreloadAndArchive".to_string(), "txt".to_string(), Box::new(get_example_data("PreloadAndArchive.txt"))),
+	];
+	let summary = VPKv2::rebuild(&tmp_dir, "vpk_test", &mut ents, &CreateOptionsV2::default()).expect("Rebuild failed");
+	assert_eq!(summary, RebuildSummaryV2 { kept_archives : vec![0], ..Default::default() });
+
+	let vpk = VPKv2::open_from_path(&tmp_dir.join("vpk_test_dir.vpk")).expect("Couldn't open VPK");
+	assert!(vpk.get_handles().iter().all(|h| h.crc() != 0));
+	assert!(vpk.validate_entries().unwrap().is_empty());
+
+	std::fs::remove_dir_all(tmp_dir).unwrap();
+}
+
+#[test]
+/// Creates a VPK with duplicated content, checking duplicates share data and still read back with valid CRCs.
+fn create_deduplicated() {
+	use valve_resource_tools::resource::vpk::v2::*;
+	use valve_resource_tools::resource::vpk::prelude::*;
+
+	let entries = || vec![
+		/* 18 bytes */ example_entry("dedup", "a", false, 0, "ArchiveOnly.txt"),
+		/* Same as "a" */ example_entry("dedup", "b", false, 0, "ArchiveOnly.txt"),
+		/* 13 bytes, the data after the preload differs from "a" */ example_entry("dedup", "c", false, 5, "ArchiveOnly.txt"),
+		/* 28 bytes */ example_entry("dedup", "d", false, 0, "EmbededArchiveOnly.txt"),
+		/* 28 bytes, embedded so not shared with "d" */ example_entry("dedup", "e", true, 0, "EmbededArchiveOnly.txt"),
+		/* Same as "e" */ example_entry("dedup", "f", true, 0, "EmbededArchiveOnly.txt"),
+	];
+
+	let tmp_dir = get_tmp_dir();
+	let options = CreateOptionsV2 { deduplicate : true, ..Default::default() };
+	let summary = VPKv2::create_with_options(&tmp_dir, "vpk_test", &mut entries(), &options).expect("Create failed");
+	assert_eq!(summary, CreateSummaryV2 { deduplicated_entries : 2, bytes_saved : 18 + 28 });
+	assert_eq!(std::fs::metadata(tmp_dir.join("vpk_test_000.vpk")).unwrap().len(), 18 + 13 + 28);
+
+	let vpk = VPKv2::open_from_path(&tmp_dir.join("vpk_test_dir.vpk")).expect("Couldn't open VPK");
+	let a = vpk.get_handle_from_path("dedup/a.txt").unwrap();
+	let b = vpk.get_handle_from_path("dedup/b.txt").unwrap();
+	assert_eq!((a.archive_index(), a.offset()), (b.archive_index(), b.offset()));
+	assert!(vpk.validate_archive().is_empty());
+	assert!(vpk.validate_entries().unwrap().is_empty());
+	for (filename, data) in [("a", "ArchiveOnly.txt"), ("b", "ArchiveOnly.txt"), ("c", "ArchiveOnly.txt"), ("f", "EmbededArchiveOnly.txt")] {
+		let path = format!("dedup/{}.txt", filename);
+		let mut buf = Vec::new();
+		vpk.get_entry_from_path(&path).unwrap().read_to_end(&mut buf).unwrap();
+		let mut content = Vec::new();
+		get_example_data(data).read_to_end(&mut content).unwrap();
+		if !do_vecs_match(&buf, &content) { panic!("File entry \"{}\" does not match original content", path) }
+	}
+
+	/* Nothing is shared by default */
+	let plain = get_tmp_dir();
+	assert_eq!(VPKv2::create_with_options(&plain, "vpk_test", &mut entries(), &CreateOptionsV2::default()).unwrap(), CreateSummaryV2::default());
+	assert_eq!(std::fs::metadata(plain.join("vpk_test_000.vpk")).unwrap().len(), 18 + 18 + 13 + 28);
+
+	std::fs::remove_dir_all(tmp_dir).unwrap();
+	std::fs::remove_dir_all(plain).unwrap();
+}
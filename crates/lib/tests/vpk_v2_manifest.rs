@@ -0,0 +1,54 @@
+mod common;
+use common::*;
+
+use valve_resource_tools::resource::vpk::VPKv2;
+use valve_resource_tools::resource::vpk::v2::*;
+use valve_resource_tools::resource::vpk::prelude::*;
+
+#[test]
+fn manifest() {
+	let vpk = VPKv2::open_from_path(&get_example_path("vpk_signed_dir.vpk")).unwrap();
+	let manifest = vpk.manifest();
+
+	assert_eq!(manifest.schema_version, MANIFEST_SCHEMA_VERSION);
+	assert_eq!(manifest.header.version, 2);
+	assert_eq!(manifest.header.tree_size, 202);
+	assert_eq!(manifest.header.file_data_section_size, 28);
+	assert_eq!(manifest.header.signature_section_size, 298);
+	assert_eq!(manifest.archive_count, 1);
+
+	let paths : Vec<&str> = manifest.entries.iter().map(|e| e.path.as_str()).collect();
+	assert_eq!(paths, vec![
+		"testing-folder/ArchiveOnly.txt",
+		"testing-folder/EmbededArchiveOnly.txt",
+		"testing-folder/PreloadAndArchive.txt",
+		"testing-folder/PreloadOnly.txt",
+	]);
+	assert_eq!(manifest.entries[2], ManifestEntry {
+		path : "testing-folder/PreloadAndArchive.txt".to_string(),
+		crc : vpk.get_handle_from_path("testing-folder/PreloadAndArchive.txt").unwrap().crc(),
+		preload_size : 21,
+		archive_index : 0,
+		embedded : false,
+		offset : 18,
+		length : 21,
+	});
+	assert!(manifest.entries[1].embedded);
+	assert_eq!(manifest.entries[1].archive_index, 0x7fff);
+
+	/* Checksums are hex, the tree checksum is the MD5 of the tree */
+	let dir = std::fs::read(get_example_path("vpk_signed_dir.vpk")).unwrap();
+	let tree_md5 = md5::compute(&dir[28..28 + 202]);
+	assert_eq!(manifest.other_md5.tree_checksum, format!("{:x}", tree_md5));
+	assert_eq!(manifest.archive_md5.len(), 2);
+	assert_eq!(manifest.archive_md5[0].md5.len(), 32);
+	assert_eq!(manifest.signature.as_ref().unwrap().public_key.len(), 162 * 2);
+
+	let json = manifest.to_json();
+	assert!(json.contains("\"schema_version\": 1"));
+	assert!(json.contains("\"tree_checksum\""));
+	assert_eq!(Manifest::from_json(&json).unwrap(), manifest);
+	assert!(Manifest::from_json("{}").is_err());
+
+	assert!(open_test_vpk().manifest().signature.is_none());
+}
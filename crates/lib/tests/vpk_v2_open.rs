@@ -16,4 +16,187 @@ fn open_read_entry() {
 
 	let vpk = open_test_vpk();
 	vpk.get_entry_from_path("PreloadAndArchive");
-}
\ No newline at end of file
+}
+
+#[test]
+fn list_entries() {
+	use valve_resource_tools::resource::vpk::prelude::*;
+
+	let vpk = open_test_vpk();
+	let mut paths = vpk.list_entries();
+	paths.sort_unstable();
+	assert_eq!(paths, vec![
+		"testing-folder/ArchiveOnly.txt",
+		"testing-folder/EmbededArchiveOnly.txt",
+		"testing-folder/PreloadAndArchive.txt",
+		"testing-folder/PreloadOnly.txt",
+	]);
+
+	let handle = vpk.get_handle_from_path("testing-folder/PreloadAndArchive.txt").unwrap();
+	assert_eq!(handle.path(), "testing-folder/PreloadAndArchive.txt");
+	assert_eq!(handle.total_size(), 42);
+	assert_eq!(handle.preload_size(), 21);
+	assert_eq!(handle.archive_size(), 21);
+	assert_eq!(handle.archive_index(), 0);
+	assert_eq!(handle.offset(), 18);
+	assert!(!handle.is_embedded());
+
+	let handle = vpk.get_handle_from_path("testing-folder/EmbededArchiveOnly.txt").unwrap();
+	assert!(handle.is_embedded());
+	assert_eq!(handle.total_size(), 28);
+	assert_eq!(handle.preload_size(), 0);
+
+	assert!(vpk.get_handle_from_path("testing-folder/PreloadOnly.txt").unwrap().is_preload_only());
+	assert!(vpk.get_handle_from_path("testing-folder/Missing.txt").is_none());
+}
+
+#[test]
+/// Reads every entry from many threads at once through a single opened VPK.
+fn read_entries_in_parallel() {
+	use std::io::Read;
+	use valve_resource_tools::resource::vpk::prelude::*;
+
+	fn assert_send_sync<T : Send + Sync>() {}
+	assert_send_sync::<valve_resource_tools::resource::vpk::VPKv2>();
+	assert_send_sync::<valve_resource_tools::resource::vpk::v2::EntryReaderV2>();
+
+	let vpk = open_test_vpk();
+	let expected : Vec<(String, Vec<u8>)> = vpk.list_entries().into_iter().map(|path| {
+		let mut buf = Vec::<u8>::new();
+		vpk.get_entry_from_path(path).unwrap().read_to_end(&mut buf).unwrap();
+		(path.to_string(), buf)
+	}).collect();
+
+	std::thread::scope(|s| {
+		for _ in 0..8 {
+			s.spawn(|| {
+				for _ in 0..100 {
+					for (path, content) in &expected {
+						let mut buf = Vec::<u8>::new();
+						vpk.get_entry_from_path(path).unwrap().read_to_end(&mut buf).unwrap();
+						assert!(do_vecs_match(&buf, content), "{} read incorrectly", path);
+					}
+				}
+			});
+		}
+	});
+}
+
+#[test]
+/// Borrows every entry from a memory mapped VPK and compares it to the original files.
+fn open_mmap_entry_bytes() {
+	use std::io::Read;
+	use valve_resource_tools::resource::vpk::v2::*;
+
+	let vpk = unsafe { VPKv2::open_from_path_mmap(&get_example_path("vpk_test_dir.vpk")) }.unwrap();
+	for f in &[
+		"PreloadOnly.txt",
+		"PreloadAndArchive.txt",
+		"ArchiveOnly.txt",
+		"EmbededArchiveOnly.txt",
+	] {
+		let mut res = Vec::<u8>::new();
+		get_example_data(f).read_to_end(&mut res).expect("Couldn't read example data");
+		let bytes = vpk.get_entry_bytes(&("testing-folder/".to_owned() + f)).unwrap();
+		assert!(do_vecs_match(&bytes.join(), &res), "{} does not match original content", f);
+	}
+
+	let bytes = vpk.get_entry_bytes("testing-folder/PreloadAndArchive.txt").unwrap();
+	assert_eq!(bytes.preload.len(), 21);
+	assert_eq!(bytes.archive.len(), 21);
+
+	/* Borrowing needs the VPK in memory */
+	assert!(open_test_vpk().get_entry_bytes("testing-folder/ArchiveOnly.txt").is_err());
+}
+
+#[test]
+/// Opens a VPK held entirely in memory and from generic `Read + Seek` sources.
+fn open_from_readers() {
+	use std::io::{Cursor, Read};
+	use std::sync::Arc;
+	use valve_resource_tools::resource::vpk::v2::*;
+	use valve_resource_tools::resource::vpk::prelude::*;
+
+	let dir = std::fs::read(get_example_path("vpk_test_dir.vpk")).unwrap();
+	let data = std::fs::read(get_example_path("vpk_test_000.vpk")).unwrap();
+
+	let in_memory = VPKv2::open_from_readers(Arc::new(dir.clone()), vec![Arc::new(data.clone())]).unwrap();
+	let seekable = VPKv2::open_from_readers(
+		Arc::new(ReadSeekAt::new(Cursor::new(dir))),
+		vec![Arc::new(ReadSeekAt::new(Cursor::new(data)))],
+	).unwrap();
+
+	for f in &[
+		"PreloadOnly.txt",
+		"PreloadAndArchive.txt",
+		"ArchiveOnly.txt",
+		"EmbededArchiveOnly.txt",
+	] {
+		let path = "testing-folder/".to_owned() + f;
+		let mut res = Vec::<u8>::new();
+		get_example_data(f).read_to_end(&mut res).expect("Couldn't read example data");
+
+		let mut buf = Vec::<u8>::new();
+		seekable.get_entry_from_path(&path).unwrap().read_to_end(&mut buf).unwrap();
+		assert!(do_vecs_match(&buf, &res), "{} does not match original content", f);
+
+		/* Buffers are in memory so entries can also be borrowed */
+		assert!(do_vecs_match(&in_memory.get_entry_bytes(&path).unwrap().join(), &res), "{} does not match original content", f);
+	}
+
+	assert!(VPKv2::open_from_readers(Arc::new(vec![0u8; 4]), Vec::new()).is_err());
+}
+
+#[test]
+/// Opens a single file VPK with all data embedded, named without the `_dir` suffix.
+fn open_standalone() {
+	use std::io::Read;
+	use valve_resource_tools::resource::vpk::v2::*;
+	use valve_resource_tools::resource::vpk::prelude::*;
+
+	let tmp_dir = get_tmp_dir();
+	let mut ents = vec![
+		EntryPrototypeV2::new(true, 0, "testing-folder".to_string(), "EmbededArchiveOnly".to_string(), "txt".to_string(), Box::new(get_example_data("EmbededArchiveOnly.txt"))),
+	];
+	VPKv2::create(&tmp_dir, "addon", &mut ents).unwrap();
+	std::fs::rename(tmp_dir.join("addon_dir.vpk"), tmp_dir.join("addon.vpk")).unwrap();
+
+	let path = tmp_dir.join("addon.vpk");
+	assert!(valve_resource_tools::resource::vpk::open(&path).is_ok());
+
+	let vpk = VPKv2::open_from_path(&path).unwrap();
+	let mut buf = Vec::<u8>::new();
+	let mut res = Vec::<u8>::new();
+	vpk.get_entry_from_path("testing-folder/EmbededArchiveOnly.txt").unwrap().read_to_end(&mut buf).unwrap();
+	get_example_data("EmbededArchiveOnly.txt").read_to_end(&mut res).unwrap();
+	assert!(do_vecs_match(&buf, &res));
+
+	std::fs::remove_dir_all(tmp_dir).unwrap();
+}
+
+#[test]
+/// Opens a set through one of its data archives and through explicitly named files.
+fn open_from_other_paths() {
+	use valve_resource_tools::resource::error::ErrorKind;
+	use valve_resource_tools::resource::vpk::v2::*;
+	use valve_resource_tools::resource::vpk::prelude::*;
+
+	let vpk = VPKv2::open_from_path(&get_example_path("vpk_test_000.vpk")).unwrap();
+	assert!(vpk.validate_archive().is_empty());
+
+	let tmp_dir = get_tmp_dir();
+	std::fs::copy(get_example_path("vpk_test_dir.vpk"), tmp_dir.join("index.bin")).unwrap();
+	std::fs::copy(get_example_path("vpk_test_000.vpk"), tmp_dir.join("chunk.bin")).unwrap();
+	let vpk = VPKv2::open_from_paths(&tmp_dir.join("index.bin"), &[tmp_dir.join("chunk.bin")]).unwrap();
+	assert!(vpk.validate_archive().is_empty());
+
+	/* A data archive without its directory file */
+	std::fs::copy(get_example_path("vpk_test_000.vpk"), tmp_dir.join("orphan_000.vpk")).unwrap();
+	assert!(matches!(VPKv2::open_from_path(&tmp_dir.join("orphan_000.vpk")), Err(ErrorKind::DoesNotExist(_))));
+
+	assert!(matches!(VPKv2::open_from_path(&tmp_dir.join("missing_dir.vpk")), Err(ErrorKind::DoesNotExist(_))));
+	assert!(matches!(VPKv2::open_from_paths(&tmp_dir.join("index.bin"), &[tmp_dir.join("missing.bin")]), Err(ErrorKind::DoesNotExist(_))));
+	assert!(matches!(valve_resource_tools::resource::vpk::open(std::path::Path::new("/")), Err(ErrorKind::DoesNotExist(_))));
+
+	std::fs::remove_dir_all(tmp_dir).unwrap();
+}
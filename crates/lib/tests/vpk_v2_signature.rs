@@ -0,0 +1,200 @@
+mod common;
+use common::*;
+
+use valve_resource_tools::resource::error::ErrorKind;
+use valve_resource_tools::resource::vpk::VPKv2;
+use valve_resource_tools::resource::vpk::prelude::*;
+
+#[test]
+fn open_signed() {
+	let vpk = VPKv2::open_from_path(&get_example_path("vpk_signed_dir.vpk")).unwrap();
+
+	let signature = vpk.signature().expect("signed VPK has no signature section");
+	assert_eq!(signature.public_key.len(), 162);
+	assert_eq!(signature.signature.len(), 128);
+	assert_eq!(signature.size(), 298);
+
+	assert!(vpk.validate_other().is_ok());
+	assert!(vpk.validate_archive().is_empty());
+}
+
+#[test]
+fn validate_signature() {
+	let vpk = VPKv2::open_from_path(&get_example_path("vpk_signed_dir.vpk")).unwrap();
+	vpk.validate_signature().unwrap();
+}
+
+#[test]
+fn validate_signature_unsigned() {
+	let vpk = open_test_vpk();
+	assert!(vpk.signature().is_none());
+	assert!(matches!(vpk.validate_signature(), Err(ErrorKind::DoesNotExist(_))));
+}
+
+#[test]
+fn validate_signature_tampered() {
+	let tmp_dir = get_tmp_dir();
+	let dir_path = tmp_dir.join("vpk_signed_dir.vpk");
+	std::fs::copy(get_example_path("vpk_signed_000.vpk"), tmp_dir.join("vpk_signed_000.vpk")).unwrap();
+
+	/* Flip a byte inside the directory tree */
+	let mut data = std::fs::read(get_example_path("vpk_signed_dir.vpk")).unwrap();
+	data[40] ^= 0xff;
+	std::fs::write(&dir_path, data).unwrap();
+
+	let vpk = VPKv2::open_from_path(&dir_path).unwrap();
+	assert!(matches!(vpk.validate_signature(), Err(ErrorKind::ValidationFailed(_))));
+
+	std::fs::remove_dir_all(tmp_dir).unwrap();
+}
+
+fn signed_entries() -> Vec<valve_resource_tools::resource::vpk::v2::EntryPrototypeV2> {
+	use valve_resource_tools::resource::vpk::v2::*;
+
+	vec![
+		EntryPrototypeV2::new(false, 21, "testing-folder".to_string(), "PreloadAndArchive".to_string(),  "txt".to_string(), Box::new(get_example_data("PreloadAndArchive.txt"))),
+		EntryPrototypeV2::new(true,   0, "testing-folder".to_string(), "EmbededArchiveOnly".to_string(), "txt".to_string(), Box::new(get_example_data("EmbededArchiveOnly.txt"))),
+	]
+}
+
+fn read_key_file(name : &str) -> String {
+	std::fs::read_to_string(get_example_path(name)).unwrap()
+}
+
+#[test]
+/// Signs with a Valve KeyValues key pair and checks the result verifies and reads back.
+fn create_signed_valve_keys() {
+	use std::io::Read;
+	use valve_resource_tools::resource::vpk::v2::*;
+
+	let key = SigningKeyV2::from_valve_key_files(
+		&read_key_file("vpk_signing_key.privatekey.vdf"),
+		&read_key_file("vpk_signing_key.publickey.vdf"),
+	).unwrap();
+	let options = CreateOptionsV2 { signing_key : Some(key.clone()), ..Default::default() };
+
+	let tmp_dir = get_tmp_dir();
+	VPKv2::create_with_options(&tmp_dir, "signed", &mut signed_entries(), &options).unwrap();
+
+	let vpk = VPKv2::open_from_path(&tmp_dir.join("signed_dir.vpk")).unwrap();
+	vpk.validate_signature().unwrap();
+	vpk.validate_other().unwrap();
+	assert_eq!(vpk.signature().unwrap().public_key, key.public_key());
+
+	let mut buf = String::new();
+	vpk.get_entry_from_path("testing-folder/PreloadAndArchive.txt").unwrap().read_to_string(&mut buf).unwrap();
+	let mut res = String::new();
+	get_example_data("PreloadAndArchive.txt").read_to_string(&mut res).unwrap();
+	assert_eq!(buf, res);
+
+	std::fs::remove_dir_all(tmp_dir).unwrap();
+}
+
+#[test]
+/// Signs with a PEM key and checks appending re-signs the directory.
+fn create_signed_pem() {
+	use valve_resource_tools::resource::vpk::v2::*;
+
+	let key = SigningKeyV2::from_pem(&read_key_file("vpk_signing_key.pem")).unwrap();
+	let options = CreateOptionsV2 { signing_key : Some(key), ..Default::default() };
+
+	let tmp_dir = get_tmp_dir();
+	let mut entries = signed_entries();
+	let extra = entries.pop().unwrap();
+	VPKv2::create_with_options(&tmp_dir, "signed", &mut entries, &options).unwrap();
+	VPKv2::open_from_path(&tmp_dir.join("signed_dir.vpk")).unwrap().validate_signature().unwrap();
+
+	VPKv2::append_entries_with_options(&tmp_dir, "signed", &mut [extra], &options).unwrap();
+	let vpk = VPKv2::open_from_path(&tmp_dir.join("signed_dir.vpk")).unwrap();
+	assert_eq!(vpk.list_entries().len(), 2);
+	vpk.validate_signature().unwrap();
+
+	std::fs::remove_dir_all(tmp_dir).unwrap();
+}
+
+#[test]
+fn signing_key_mismatched_pair() {
+	use valve_resource_tools::resource::vpk::v2::*;
+
+	let public = read_key_file("vpk_signing_key.publickey.vdf");
+	let mut chars : Vec<char> = public.chars().collect();
+	/* Change the last digit of the modulus, which is directly followed by the exponent */
+	let i = public.rfind("0203010001").unwrap() - 1;
+	chars[i] = if chars[i] == '0' { '1' } else { '0' };
+	let public : String = chars.into_iter().collect();
+
+	let res = SigningKeyV2::from_valve_key_files(&read_key_file("vpk_signing_key.privatekey.vdf"), &public);
+	assert!(matches!(res, Err(ErrorKind::MalformedData(_))));
+}
+
+#[test]
+/// A directory file re-signed with another key still matches its own signature but not the trusted key.
+fn validate_signature_with_trusted_key() {
+	use rsa::pkcs8::{EncodePrivateKey, LineEnding};
+	use valve_resource_tools::resource::vpk::v2::*;
+
+	let trusted = public_key_from_valve_key_file(&read_key_file("vpk_signing_key.publickey.vdf")).unwrap();
+	assert_eq!(trusted, SigningKeyV2::from_pem(&read_key_file("vpk_signing_key.pem")).unwrap().public_key());
+	let options = CreateOptionsV2 { signing_key : Some(SigningKeyV2::from_pem(&read_key_file("vpk_signing_key.pem")).unwrap()), ..Default::default() };
+	let tmp_dir = get_tmp_dir();
+	VPKv2::create_with_options(&tmp_dir, "trusted", &mut signed_entries(), &options).unwrap();
+	VPKv2::open_from_path(&tmp_dir.join("trusted_dir.vpk")).unwrap().validate_signature_with_key(&trusted).unwrap();
+
+	/* The fixture was signed with a key other than the test key pair */
+	let fixture = VPKv2::open_from_path(&get_example_path("vpk_signed_dir.vpk")).unwrap();
+	fixture.validate_signature_with_key(&fixture.signature().unwrap().public_key).unwrap();
+	assert!(matches!(fixture.validate_signature_with_key(&trusted), Err(ErrorKind::ValidationFailed(_))));
+
+	let other = rsa::RsaPrivateKey::new(&mut rand::thread_rng(), 1024).unwrap();
+	let other = SigningKeyV2::from_pem(&other.to_pkcs8_pem(LineEnding::LF).unwrap()).unwrap();
+	let options = CreateOptionsV2 { signing_key : Some(other.clone()), ..Default::default() };
+	VPKv2::create_with_options(&tmp_dir, "resigned", &mut signed_entries(), &options).unwrap();
+
+	let vpk = VPKv2::open_from_path(&tmp_dir.join("resigned_dir.vpk")).unwrap();
+	vpk.validate_signature().unwrap();
+	assert!(matches!(vpk.validate_signature_with_key(&trusted), Err(ErrorKind::ValidationFailed(_))));
+	vpk.validate_signature_with_key(other.public_key()).unwrap();
+
+	assert!(matches!(open_test_vpk().validate_signature_with_key(&trusted), Err(ErrorKind::DoesNotExist(_))));
+	assert!(matches!(vpk.validate_signature_with_key(b"not a key"), Err(ErrorKind::MalformedData(_))));
+	assert!(public_key_from_pem(&read_key_file("vpk_signing_key.pem")).is_err());
+
+	std::fs::remove_dir_all(tmp_dir).unwrap();
+}
+
+#[test]
+/// Checks the signature section of a directory file signed with a 1024 bit key against the documented layout,
+/// see https://developer.valvesoftware.com/wiki/VPK_File_Format.
+/// 
+/// The fixture was signed by this library rather than by Valve, so the signed range and hash are only checked against its own signing.
+fn valve_signature_layout() {
+	use rsa::{RsaPublicKey, Pkcs1v15Sign, pkcs8::DecodePublicKey, traits::PublicKeyParts};
+	use sha2::{Sha256, Digest};
+	use valve_resource_tools::resource::vpk::v2::*;
+
+	let data = std::fs::read(get_example_path("vpk_valve_layout_dir.vpk")).unwrap();
+	let vpk = VPKv2::open_from_path(&get_example_path("vpk_valve_layout_dir.vpk")).unwrap();
+	let header = vpk.header();
+
+	/* 296 bytes: the size of the public key, a 160 byte key, the size of the signature then a 128 byte signature */
+	assert_eq!(header.signature_section_size, 296);
+	let start = 28 + header.tree_size + header.file_data_section_size + header.archive_md5_section_size + header.other_md5_section_size;
+	let section = &data[usize::try_from(start).unwrap()..];
+	assert_eq!(section.len(), 296);
+	assert_eq!(section[0..4], 160u32.to_le_bytes());
+	assert_eq!(section[164..168], 128u32.to_le_bytes());
+	assert_eq!(vpk.signature().unwrap().public_key, &section[4..164]);
+
+	/* A SubjectPublicKeyInfo with a 1024 bit modulus and a single byte exponent */
+	let key = RsaPublicKey::from_public_key_der(&section[4..164]).unwrap();
+	assert_eq!(key.n().bits(), 1024);
+	assert_eq!(key.e(), &rsa::BigUint::from(17u32));
+
+	/* PKCS#1 v1.5 with SHA-256 over every byte before the section */
+	key.verify(Pkcs1v15Sign::new::<Sha256>(), &Sha256::digest(&data[..data.len() - 296]), &section[168..]).unwrap();
+
+	let trusted = public_key_from_valve_key_file(&read_key_file("vpk_valve_layout_key.publickey.vdf")).unwrap();
+	vpk.validate_signature_with_key(&trusted).unwrap();
+	vpk.validate_other().unwrap();
+	assert!(vpk.validate_archive().is_empty());
+}
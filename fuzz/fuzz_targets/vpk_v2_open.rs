@@ -0,0 +1,33 @@
+//! Opens arbitrary bytes as a VPK v2 directory file, then reads and validates everything it claims to hold.
+//! 
+//! Any input must fail with an error rather than panic. Run from this folder with a nightly toolchain,
+//! seeding the corpus with the test VPKs:
+//! 
+//! `cargo fuzz run vpk_v2_open corpus/vpk_v2_open ../crates/lib/test-data`
+
+#![no_main]
+
+use std::sync::Arc;
+
+use libfuzzer_sys::fuzz_target;
+use valve_resource_tools::resource::vpk::{VPKv2, Reader};
+use valve_resource_tools::resource::vpk::prelude::*;
+
+fuzz_target!(|data : &[u8]| {
+	/* The directory file doubles as the only data archive so entries pointing into archive 0 are read too */
+	let dir : Reader = Arc::new(data.to_vec());
+	let Ok(vpk) = VPKv2::open_from_readers(dir.clone(), vec![dir]) else { return; };
+
+	for handle in vpk.get_handles() {
+		let _ = vpk.get_entry_bytes(handle.path());
+		if let Ok(mut reader) = vpk.get_entry_from_path(handle.path()) {
+			let _ = std::io::copy(&mut reader, &mut std::io::sink());
+		}
+	}
+
+	let _ = vpk.validate_archive();
+	let _ = vpk.validate_other();
+	let _ = vpk.validate_entries();
+	let _ = vpk.validate_signature();
+	let _ = vpk.manifest();
+});